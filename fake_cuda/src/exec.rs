@@ -0,0 +1,646 @@
+//! CPU interpreter for parsed kernels.
+
+use std::{
+    ops::{Add, Mul, Sub},
+    ptr, thread,
+};
+
+use crate::ptx::{ArithOp, Compare, Cvt, Function, Instruction, Kernel, MulMode, Operand, Rounding, Special, Type};
+
+#[derive(Clone, Copy)]
+pub struct Dim {
+    pub grid: u32,
+    pub block: u32,
+}
+
+/// Run `kernel` for every thread of a one-dimensional launch.
+///
+/// # Safety
+/// `params` must hold valid device pointers for every kernel parameter and the kernel must only
+/// access memory within the buffers they point to.
+pub unsafe fn launch(kernel: &Kernel, dim: Dim, params: &[u64]) {
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(dim.grid.max(1) as usize) as u32;
+    let blocks_per_worker = dim.grid.div_ceil(workers.max(1));
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            let first = worker * blocks_per_worker;
+            let last = ((worker + 1) * blocks_per_worker).min(dim.grid);
+            scope.spawn(move || {
+                let mut registers = vec![0u64; kernel.registers];
+                for ctaid in first..last {
+                    for tid in 0..dim.block {
+                        let thread = ThreadState { tid, ctaid, dim, params };
+                        thread.run(kernel, &mut registers);
+                    }
+                }
+            });
+        }
+    });
+}
+
+struct ThreadState<'a> {
+    tid: u32,
+    ctaid: u32,
+    dim: Dim,
+    params: &'a [u64],
+}
+
+impl ThreadState<'_> {
+    fn run(&self, kernel: &Kernel, regs: &mut [u64]) {
+        for instruction in &kernel.instructions {
+            match *instruction {
+                Instruction::Ret => return,
+                Instruction::Mov { ty, dst, src } => regs[dst] = truncate(ty, self.read(regs, src)),
+                Instruction::LdParam { dst, param } => regs[dst] = self.params[param],
+                Instruction::Ld { ty, dst, addr, offset } => {
+                    let addr = self.read(regs, addr).wrapping_add(offset as u64);
+                    regs[dst] = unsafe { load(ty, addr) };
+                }
+                Instruction::St { ty, addr, offset, src } => {
+                    let addr = self.read(regs, addr).wrapping_add(offset as u64);
+                    unsafe { store(ty, addr, self.read(regs, src)) };
+                }
+                Instruction::Cvt { cvt, dst, src } => regs[dst] = convert(&cvt, self.read(regs, src)),
+                Instruction::Mul { mode, ty, dst, a, b } => {
+                    regs[dst] = multiply(mode, ty, false, self.read(regs, a), self.read(regs, b), None)
+                }
+                Instruction::Mad { mode, ty, sat, dst, a, b, c } => {
+                    let c = self.read(regs, c);
+                    regs[dst] = multiply(mode, ty, sat, self.read(regs, a), self.read(regs, b), Some(c))
+                }
+                Instruction::Brev { ty, dst, src } => {
+                    let value = self.read(regs, src).reverse_bits() >> (64 - ty.bits());
+                    regs[dst] = value;
+                }
+                Instruction::Bfe { ty, dst, a, b, c } => {
+                    regs[dst] = bfe(ty, self.read(regs, a), self.read(regs, b), self.read(regs, c))
+                }
+                Instruction::Bfi { ty, dst, a, b, c, d } => {
+                    let (a, b) = (self.read(regs, a), self.read(regs, b));
+                    regs[dst] = bfi(ty, a, b, self.read(regs, c), self.read(regs, d))
+                }
+                Instruction::Shl { ty, dst, a, b } => {
+                    let shift = self.read(regs, b) as u32;
+                    let value = if shift >= ty.bits() { 0 } else { self.read(regs, a) << shift };
+                    regs[dst] = truncate(ty, value);
+                }
+                Instruction::Shr { ty, dst, a, b } => {
+                    let shift = self.read(regs, b) as u32;
+                    let value = if ty.is_signed() {
+                        (sign_extend(ty, self.read(regs, a)) >> shift.min(ty.bits() - 1)) as u64
+                    } else if shift >= ty.bits() {
+                        0
+                    } else {
+                        truncate(ty, self.read(regs, a)) >> shift
+                    };
+                    regs[dst] = truncate(ty, value);
+                }
+                Instruction::Div { ty, dst, a, b } => {
+                    regs[dst] = divide(ty, self.read(regs, a), self.read(regs, b)).0
+                }
+                Instruction::Rem { ty, dst, a, b } => {
+                    regs[dst] = divide(ty, self.read(regs, a), self.read(regs, b)).1
+                }
+                Instruction::Setp { cmp, ty, ftz, p, q, a, b } => {
+                    let result = compare(cmp, ty, ftz, self.read(regs, a), self.read(regs, b));
+                    regs[p] = result as u64;
+                    if let Some(q) = q {
+                        regs[q] = !result as u64;
+                    }
+                }
+                Instruction::Selp { ty, dst, a, b, c } => {
+                    let value = if self.read(regs, c) != 0 { self.read(regs, a) } else { self.read(regs, b) };
+                    regs[dst] = truncate(ty, value);
+                }
+                Instruction::MinMax { ty, max, ftz, nan, dst, a, b } => {
+                    regs[dst] = min_max(ty, max, ftz, nan, self.read(regs, a), self.read(regs, b))
+                }
+                Instruction::Unary { function, rnd, ftz, dst, src } => {
+                    let value = f32::from_bits(self.read(regs, src) as u32);
+                    regs[dst] = unary(function, rnd, ftz, value).to_bits() as u64;
+                }
+                Instruction::Arith { op, ty, rnd, ftz, sat, dst, a, b } => {
+                    regs[dst] = arith(op, ty, rnd, ftz, sat, self.read(regs, a), self.read(regs, b))
+                }
+                Instruction::Fma { ty, rnd, ftz, sat, relu, dst, a, b, c } => {
+                    let (a, b, c) = (self.read(regs, a), self.read(regs, b), self.read(regs, c));
+                    regs[dst] = fma(ty, rnd, ftz, sat, relu, [a, b, c])
+                }
+            }
+        }
+    }
+
+    fn read(&self, regs: &[u64], operand: Operand) -> u64 {
+        match operand {
+            Operand::Reg(index) => regs[index],
+            Operand::Imm(value) => value,
+            Operand::Special(Special::TidX) => self.tid as u64,
+            Operand::Special(Special::NtidX) => self.dim.block as u64,
+            Operand::Special(Special::CtaidX) => self.ctaid as u64,
+            Operand::Special(Special::NctaidX) => self.dim.grid as u64,
+        }
+    }
+}
+
+fn truncate(ty: Type, value: u64) -> u64 {
+    if ty.bits() == 64 {
+        value
+    } else {
+        value & ((1u64 << ty.bits()) - 1)
+    }
+}
+
+fn sign_extend(ty: Type, value: u64) -> i64 {
+    let shift = 64 - ty.bits();
+    ((value << shift) as i64) >> shift
+}
+
+/// Value of an integer register interpreted according to its type.
+fn integer(ty: Type, value: u64) -> i128 {
+    if ty.is_signed() {
+        sign_extend(ty, value) as i128
+    } else {
+        truncate(ty, value) as i128
+    }
+}
+
+unsafe fn load(ty: Type, addr: u64) -> u64 {
+    let value = match ty.bits() {
+        8 => ptr::read_unaligned(addr as *const u8) as u64,
+        16 => ptr::read_unaligned(addr as *const u16) as u64,
+        32 => ptr::read_unaligned(addr as *const u32) as u64,
+        _ => ptr::read_unaligned(addr as *const u64),
+    };
+    if ty.is_signed() {
+        sign_extend(ty, value) as u64
+    } else {
+        value
+    }
+}
+
+unsafe fn store(ty: Type, addr: u64, value: u64) {
+    match ty.bits() {
+        8 => ptr::write_unaligned(addr as *mut u8, value as u8),
+        16 => ptr::write_unaligned(addr as *mut u16, value as u16),
+        32 => ptr::write_unaligned(addr as *mut u32, value as u32),
+        _ => ptr::write_unaligned(addr as *mut u64, value),
+    }
+}
+
+fn multiply(mode: MulMode, ty: Type, sat: bool, a: u64, b: u64, c: Option<u64>) -> u64 {
+    let bits = ty.bits();
+    // Full product of two 64-bit values only fits into 128 bits with matching signedness
+    let (product, high) = if ty.is_signed() {
+        let product = integer(ty, a) * integer(ty, b);
+        (product, product >> bits)
+    } else {
+        let product = (truncate(ty, a) as u128) * (truncate(ty, b) as u128);
+        (product as i128, (product >> bits) as i128)
+    };
+    match mode {
+        MulMode::Lo => {
+            let c = c.map(|c| integer(ty, c)).unwrap_or(0);
+            truncate(ty, product.wrapping_add(c) as u64)
+        }
+        MulMode::Hi => {
+            let result = high + c.map(|c| integer(ty, c)).unwrap_or(0);
+            if sat {
+                truncate(ty, result.clamp(i32::MIN as i128, i32::MAX as i128) as u64)
+            } else {
+                truncate(ty, result as u64)
+            }
+        }
+        MulMode::Wide => {
+            let wide = if ty.is_signed() { signed_type(bits * 2) } else { unsigned_type(bits * 2) };
+            let c = c.map(|c| integer(wide, c)).unwrap_or(0);
+            truncate(wide, product.wrapping_add(c) as u64)
+        }
+    }
+}
+
+/// Quotient and remainder, division by zero gives all ones and the dividend like NVIDIA hardware.
+fn divide(ty: Type, a: u64, b: u64) -> (u64, u64) {
+    let (a, b) = (integer(ty, a), integer(ty, b));
+    if b == 0 {
+        return (truncate(ty, u64::MAX), truncate(ty, a as u64));
+    }
+    // Operands are at most 64 bits wide, so MIN / -1 can't overflow and wraps on truncation
+    (truncate(ty, (a / b) as u64), truncate(ty, (a % b) as u64))
+}
+
+fn compare(cmp: Compare, ty: Type, ftz: bool, a: u64, b: u64) -> bool {
+    let ordering = if ty.is_float() {
+        let (a, b) = if ftz { (flush(ty, a), flush(ty, b)) } else { (a, b) };
+        float_value(ty, a).partial_cmp(&float_value(ty, b))
+    } else {
+        Some(integer(ty, a).cmp(&integer(ty, b)))
+    };
+    let Some(ordering) = ordering else {
+        return matches!(
+            cmp,
+            Compare::Equ | Compare::Neu | Compare::Ltu | Compare::Leu | Compare::Gtu | Compare::Geu | Compare::Nan
+        );
+    };
+    match cmp {
+        Compare::Eq | Compare::Equ => ordering.is_eq(),
+        Compare::Ne | Compare::Neu => ordering.is_ne(),
+        Compare::Lt | Compare::Ltu => ordering.is_lt(),
+        Compare::Le | Compare::Leu => ordering.is_le(),
+        Compare::Gt | Compare::Gtu => ordering.is_gt(),
+        Compare::Ge | Compare::Geu => ordering.is_ge(),
+        Compare::Num => true,
+        Compare::Nan => false,
+    }
+}
+
+fn signed_type(bits: u32) -> Type {
+    match bits {
+        16 => Type::S16,
+        32 => Type::S32,
+        _ => Type::S64,
+    }
+}
+
+fn unsigned_type(bits: u32) -> Type {
+    match bits {
+        16 => Type::U16,
+        32 => Type::U32,
+        _ => Type::U64,
+    }
+}
+
+fn bit(value: u64, n: u32) -> u64 {
+    (value >> n) & 1
+}
+
+fn bfe(ty: Type, a: u64, pos: u64, len: u64) -> u64 {
+    let msb = ty.bits() - 1;
+    // Like NVIDIA hardware, 64-bit variants use whole 32 bits of position and length
+    let (pos, len) = if ty.bits() == 32 {
+        (pos & 0xff, len & 0xff)
+    } else {
+        (pos & 0xffff_ffff, len & 0xffff_ffff)
+    };
+    let sbit = if !ty.is_signed() || len == 0 {
+        0
+    } else {
+        bit(a, (pos + len - 1).min(msb as u64) as u32)
+    };
+    let mut d = 0;
+    for i in 0..=msb as u64 {
+        let value = if i < len && pos + i <= msb as u64 {
+            bit(a, (pos + i) as u32)
+        } else {
+            sbit
+        };
+        d |= value << i;
+    }
+    d
+}
+
+fn bfi(ty: Type, a: u64, b: u64, pos: u64, len: u64) -> u64 {
+    let msb = ty.bits() as u64 - 1;
+    let (pos, len) = if ty.bits() == 32 {
+        (pos & 0xff, len & 0xff)
+    } else {
+        (pos & 0xffff_ffff, len & 0xffff_ffff)
+    };
+    let mut f = truncate(ty, b);
+    for i in 0..len {
+        if pos + i > msb {
+            break;
+        }
+        let mask = 1u64 << (pos + i);
+        f = (f & !mask) | (bit(a, i as u32) << (pos + i));
+    }
+    f
+}
+
+fn min_max(ty: Type, max: bool, ftz: bool, nan: bool, a: u64, b: u64) -> u64 {
+    if let Some(lane) = ty.lane() {
+        return per_lane([a, b, 0], |[a, b, _]| min_max(lane, max, ftz, nan, a, b));
+    }
+    let (a, b) = if ftz { (flush_arith(ty, a), flush_arith(ty, b)) } else { (a, b) };
+    let (x, y) = (float_value(ty, a), float_value(ty, b));
+    if (x.is_nan() && y.is_nan()) || (nan && (x.is_nan() || y.is_nan())) {
+        u64::MAX >> (65 - ty.bits())
+    } else if x.is_nan() {
+        b
+    } else if y.is_nan() {
+        a
+    } else if max {
+        float_bits(ty, Rounding::Rn, x.max(y))
+    } else {
+        float_bits(ty, Rounding::Rn, x.min(y))
+    }
+}
+
+fn flush_f16(value: f16) -> f16 {
+    if value.is_subnormal() {
+        f16::from_bits(value.to_bits() & 0x8000)
+    } else {
+        value
+    }
+}
+
+fn flush_f32(value: f32) -> f32 {
+    if value.is_subnormal() {
+        f32::from_bits(value.to_bits() & 0x8000_0000)
+    } else {
+        value
+    }
+}
+
+fn unary(function: Function, rnd: Option<Rounding>, ftz: bool, mut value: f32) -> f32 {
+    // Approximate functions treat subnormal inputs as zero
+    if ftz || rnd.is_none() {
+        value = flush_f32(value);
+    }
+    let input = value as f64;
+    let precise = match function {
+        Function::Rcp => input.recip(),
+        Function::Sqrt => input.sqrt(),
+        Function::Rsqrt => input.sqrt().recip(),
+        Function::Sin => input.sin(),
+        Function::Cos => input.cos(),
+        Function::Lg2 => input.log2(),
+    };
+    let result = with_rounding(rnd.unwrap_or(Rounding::Rn), || precise as f32);
+    if ftz {
+        flush_f32(result)
+    } else {
+        result
+    }
+}
+
+fn arith(op: ArithOp, ty: Type, rnd: Rounding, ftz: bool, sat: bool, a: u64, b: u64) -> u64 {
+    fn apply<T: Add<Output = T> + Sub<Output = T> + Mul<Output = T>>(op: ArithOp, a: T, b: T) -> T {
+        match op {
+            ArithOp::Add => a + b,
+            ArithOp::Sub => a - b,
+            ArithOp::Mul => a * b,
+        }
+    }
+    if let Some(lane) = ty.lane() {
+        return per_lane([a, b, 0], |[a, b, _]| arith(op, lane, rnd, ftz, sat, a, b));
+    }
+    let (a, b) = if ftz { (flush_arith(ty, a), flush_arith(ty, b)) } else { (a, b) };
+    // Host arithmetic rounds once in the current rounding mode, just like the GPU
+    let result = match ty {
+        // Sums and products of f16 are exact in f64, only the conversion rounds
+        Type::F16 => {
+            let (a, b) = (f16::from_bits(a as u16) as f64, f16::from_bits(b as u16) as f64);
+            (apply(op, a, b) as f16).to_bits() as u64
+        }
+        Type::BF16 => {
+            let (a, b) = (float_value(ty, a), float_value(ty, b));
+            match op {
+                ArithOp::Add => round_bf16(a, b),
+                ArithOp::Sub => round_bf16(a, -b),
+                ArithOp::Mul => round_bf16(a * b, -0.0),
+            }
+        }
+        Type::F32 => {
+            let (a, b) = (f32::from_bits(a as u32), f32::from_bits(b as u32));
+            with_rounding(rnd, || apply(op, a, b)).to_bits() as u64
+        }
+        _ => with_rounding(rnd, || apply(op, f64::from_bits(a), f64::from_bits(b))).to_bits(),
+    };
+    arith_result(ty, ftz, sat, result)
+}
+
+fn fma(ty: Type, rnd: Rounding, ftz: bool, sat: bool, relu: bool, operands: [u64; 3]) -> u64 {
+    if let Some(lane) = ty.lane() {
+        return per_lane(operands, |operands| fma(lane, rnd, ftz, sat, relu, operands));
+    }
+    let [a, b, c] = operands.map(|value| if ftz { flush_arith(ty, value) } else { value });
+    let result = match ty {
+        Type::F16 => {
+            // Exact results are either representable in f64 or too far from ties of f16 for the
+            // rounding to f64 to make a difference
+            let [a, b, c] = [a, b, c].map(|value| f16::from_bits(value as u16) as f64);
+            (a.mul_add(b, c) as f16).to_bits() as u64
+        }
+        // Products of bf16 are exact in f64
+        Type::BF16 => {
+            let [a, b, c] = [a, b, c].map(|value| float_value(ty, value));
+            round_bf16(a * b, c)
+        }
+        Type::F32 => {
+            let [a, b, c] = [a, b, c].map(|value| f32::from_bits(value as u32));
+            with_rounding(rnd, || a.mul_add(b, c)).to_bits() as u64
+        }
+        _ => {
+            let [a, b, c] = [a, b, c].map(f64::from_bits);
+            with_rounding(rnd, || a.mul_add(b, c)).to_bits()
+        }
+    };
+    let result = arith_result(ty, ftz, sat, result);
+    if relu {
+        clamp_negative(ty, result)
+    } else {
+        result
+    }
+}
+
+/// `.relu`, negative results other than NaN become +0.0.
+fn clamp_negative(ty: Type, result: u64) -> u64 {
+    let negative = result >> (ty.bits() - 1) == 1;
+    if negative && !float_value(ty, result).is_nan() {
+        0
+    } else {
+        result
+    }
+}
+
+/// Bits of `a + b` rounded once to the nearest bf16. The sum is first rounded to odd in f64 and
+/// then in f32, which both have more than twice the precision of bf16, so rounding to nearest from
+/// there gives the same result as rounding the exact sum.
+fn round_bf16(a: f64, b: f64) -> u64 {
+    let sum = a + b;
+    // Rounding error of the sum, which is exact itself
+    let error = if sum.is_finite() { (a - (sum - (sum - a))) + (b - (sum - a)) } else { 0.0 };
+    let wide = if error != 0.0 && sum.to_bits() & 1 == 0 {
+        let away_from_zero = (error > 0.0) == (sum > 0.0);
+        f64::from_bits(if away_from_zero { sum.to_bits() + 1 } else { sum.to_bits() - 1 })
+    } else {
+        sum
+    };
+    let mut narrow = wide as f32;
+    if narrow.is_finite() && narrow as f64 != wide && narrow.to_bits() & 1 == 0 {
+        let away_from_zero = wide.abs() > narrow.abs() as f64;
+        narrow = f32::from_bits(if away_from_zero { narrow.to_bits() + 1 } else { narrow.to_bits() - 1 });
+    }
+    let bits = narrow.to_bits();
+    if narrow.is_nan() {
+        return (bits >> 16) as u64;
+    }
+    ((bits + 0x7fff + ((bits >> 16) & 1)) >> 16) as u64
+}
+
+/// Apply `f` to both lanes of packed operands.
+fn per_lane(operands: [u64; 3], f: impl Fn([u64; 3]) -> u64) -> u64 {
+    let lane = |index: u32| f(operands.map(|value| (value >> (16 * index)) & 0xffff)) << (16 * index);
+    lane(0) | lane(1)
+}
+
+fn flush_arith(ty: Type, bits: u64) -> u64 {
+    if ty == Type::F16 {
+        flush_f16(f16::from_bits(bits as u16)).to_bits() as u64
+    } else {
+        flush(ty, bits)
+    }
+}
+
+/// Apply `.ftz` and `.sat` to the result of float arithmetic, NaNs become the canonical NaN.
+fn arith_result(ty: Type, ftz: bool, sat: bool, mut result: u64) -> u64 {
+    if float_value(ty, result).is_nan() {
+        result = u64::MAX >> (65 - ty.bits());
+    }
+    if ftz {
+        result = flush_arith(ty, result);
+    }
+    if sat {
+        result = saturate(ty, result);
+    }
+    result
+}
+
+extern "C" {
+    #[link_name = "llvm.get.rounding"]
+    fn llvm_get_rounding() -> u32;
+    #[link_name = "llvm.set.rounding"]
+    fn llvm_set_rounding(r: u32);
+}
+
+fn with_rounding<T>(rnd: Rounding, f: impl FnOnce() -> T) -> T {
+    let mode = match rnd {
+        Rounding::Rzi | Rounding::Rz => 0,
+        Rounding::Default | Rounding::Rni | Rounding::Rn => 1,
+        Rounding::Rpi | Rounding::Rp => 2,
+        Rounding::Rmi | Rounding::Rm => 3,
+    };
+    let old = unsafe { llvm_get_rounding() };
+    unsafe { llvm_set_rounding(mode) };
+    let result = f();
+    unsafe { llvm_set_rounding(old) };
+    result
+}
+
+/// Widen a float register value to `f64`, which represents all narrower values exactly.
+fn float_value(ty: Type, value: u64) -> f64 {
+    match ty {
+        Type::F16 => f16::from_bits(value as u16) as f64,
+        Type::BF16 => f32::from_bits((value as u32) << 16) as f64,
+        Type::F32 => f32::from_bits(value as u32) as f64,
+        _ => f64::from_bits(value),
+    }
+}
+
+/// Round `value` to the float type `ty` and return its bits.
+fn float_bits(ty: Type, rnd: Rounding, value: f64) -> u64 {
+    // bf16 only rounds to nearest, adding -0.0 keeps the sign of zeros
+    if ty == Type::BF16 {
+        return round_bf16(value, -0.0);
+    }
+    with_rounding(rnd, || match ty {
+        Type::F16 => (value as f32 as f16).to_bits() as u64,
+        Type::F32 => (value as f32).to_bits() as u64,
+        _ => value.to_bits(),
+    })
+}
+
+fn round_integer(rnd: Rounding, value: f64) -> f64 {
+    match rnd {
+        Rounding::Rzi | Rounding::Rz => value.trunc(),
+        Rounding::Rmi | Rounding::Rm => value.floor(),
+        Rounding::Rpi | Rounding::Rp => value.ceil(),
+        Rounding::Default | Rounding::Rni | Rounding::Rn => value.round_ties_even(),
+    }
+}
+
+fn integer_range(ty: Type) -> (i128, i128) {
+    if ty.is_signed() {
+        (-(1i128 << (ty.bits() - 1)), (1i128 << (ty.bits() - 1)) - 1)
+    } else {
+        (0, (1i128 << ty.bits()) - 1)
+    }
+}
+
+fn saturate(ty: Type, bits: u64) -> u64 {
+    let value = float_value(ty, bits);
+    if value.is_nan() || value <= 0.0 {
+        float_bits(ty, Rounding::Rn, 0.0)
+    } else if value > 1.0 {
+        float_bits(ty, Rounding::Rn, 1.0)
+    } else {
+        bits
+    }
+}
+
+fn flush(ty: Type, bits: u64) -> u64 {
+    if ty == Type::F32 {
+        flush_f32(f32::from_bits(bits as u32)).to_bits() as u64
+    } else {
+        bits
+    }
+}
+
+fn convert(cvt: &Cvt, value: u64) -> u64 {
+    let Cvt { to, from, rnd, ftz, sat, relu } = *cvt;
+    match (from.is_float(), to.is_float()) {
+        (false, false) => {
+            let value = integer(from, value);
+            let value = if sat {
+                let (min, max) = integer_range(to);
+                value.clamp(min, max)
+            } else {
+                value
+            };
+            truncate(to, value as u64)
+        }
+        (true, true) => {
+            // Like NVIDIA hardware, f32 to f16 conversions only flush subnormal inputs
+            // when explicit integer rounding is used
+            let flush_input = ftz && from == Type::F32 && (to != Type::F16 || rnd.is_integer());
+            let value = if flush_input { flush(from, value) } else { value };
+            let mut result = if rnd.is_integer() {
+                float_bits(to, Rounding::Rn, round_integer(rnd, float_value(from, value)))
+            } else {
+                float_bits(to, rnd, float_value(from, value))
+            };
+            if ftz {
+                result = flush(to, result);
+            }
+            if sat {
+                result = saturate(to, result);
+            }
+            if relu {
+                result = clamp_negative(to, result);
+            }
+            result
+        }
+        (true, false) => {
+            let value = if ftz { flush(from, value) } else { value };
+            let value = float_value(from, value);
+            if value.is_nan() {
+                return 0;
+            }
+            let (min, max) = integer_range(to);
+            let value = (round_integer(rnd, value) as i128).clamp(min, max);
+            truncate(to, value as u64)
+        }
+        (false, true) => {
+            let value = integer(from, value) as f64;
+            let result = float_bits(to, rnd, value);
+            if sat {
+                saturate(to, result)
+            } else {
+                result
+            }
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! CPU-emulated stand-in for the CUDA driver library.
+//!
+//! Exports the subset of the driver API used by the test harness and interprets the PTX it
+//! generates, so the whole suite can be exercised on a machine without an NVIDIA GPU.
+//! Kernel launches and copies are synchronous, streams are only
+//! placeholders and device memory is plain host memory.
+
+#![allow(internal_features)]
+#![feature(link_llvm_intrinsics)]
+#![feature(f16)]
+// Exported functions mirror the CUDA driver API, their safety requirements are documented there
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    alloc::{self, Layout},
+    collections::HashMap,
+    ffi::{c_char, c_int, c_uint, c_void, CStr},
+    ptr,
+    sync::Mutex,
+};
+
+mod exec;
+mod ptx;
+
+type CUresult = c_uint;
+type CUdeviceptr = u64;
+
+const CUDA_SUCCESS: CUresult = 0;
+const CUDA_ERROR_INVALID_VALUE: CUresult = 1;
+const CUDA_ERROR_OUT_OF_MEMORY: CUresult = 2;
+const CUDA_ERROR_INVALID_PTX: CUresult = 218;
+const CUDA_ERROR_INVALID_HANDLE: CUresult = 400;
+const CUDA_ERROR_NOT_FOUND: CUresult = 500;
+
+const CU_JIT_INFO_LOG_BUFFER: c_uint = 3;
+const CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES: c_uint = 4;
+const CU_JIT_ERROR_LOG_BUFFER: c_uint = 5;
+const CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES: c_uint = 6;
+
+/// Name and description of every error code returned by this library.
+static ERRORS: &[(CUresult, &CStr, &CStr)] = &[
+    (CUDA_SUCCESS, c"CUDA_SUCCESS", c"no error"),
+    (CUDA_ERROR_INVALID_VALUE, c"CUDA_ERROR_INVALID_VALUE", c"invalid argument"),
+    (CUDA_ERROR_OUT_OF_MEMORY, c"CUDA_ERROR_OUT_OF_MEMORY", c"out of memory"),
+    (CUDA_ERROR_INVALID_PTX, c"CUDA_ERROR_INVALID_PTX", c"a PTX JIT compilation failed"),
+    (CUDA_ERROR_INVALID_HANDLE, c"CUDA_ERROR_INVALID_HANDLE", c"invalid resource handle"),
+    (CUDA_ERROR_NOT_FOUND, c"CUDA_ERROR_NOT_FOUND", c"named symbol not found"),
+];
+
+/// Amount of memory reported by `cuMemGetInfo_v2`.
+const TOTAL_MEMORY: usize = 4 << 30;
+const ALIGNMENT: usize = 256;
+
+struct Module {
+    kernels: Vec<ptx::Kernel>,
+}
+
+type Allocations = Mutex<Option<HashMap<CUdeviceptr, Layout>>>;
+
+/// Live device allocations, keyed by address.
+static ALLOCATIONS: Allocations = Mutex::new(None);
+
+/// Live pinned host allocations, keyed by address.
+static HOST_ALLOCATIONS: Allocations = Mutex::new(None);
+
+/// Address handed out as the only stream, all work runs synchronously anyway.
+static STREAM: u8 = 0;
+
+unsafe fn allocate(allocations: &Allocations, bytesize: usize) -> Option<CUdeviceptr> {
+    let layout = Layout::from_size_align(bytesize, ALIGNMENT).ok()?;
+    let memory = alloc::alloc(layout);
+    if memory.is_null() {
+        return None;
+    }
+    let address = memory as CUdeviceptr;
+    allocations
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(address, layout);
+    Some(address)
+}
+
+unsafe fn free(allocations: &Allocations, address: CUdeviceptr) -> CUresult {
+    let layout = allocations
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|allocations| allocations.remove(&address));
+    match layout {
+        Some(layout) => {
+            alloc::dealloc(address as *mut u8, layout);
+            CUDA_SUCCESS
+        }
+        None => CUDA_ERROR_INVALID_VALUE,
+    }
+}
+
+/// Address handed out as the only context.
+static CONTEXT: u8 = 0;
+
+#[no_mangle]
+pub unsafe extern "system" fn cuInit(_flags: c_uint) -> CUresult {
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuCtxCreate_v2(pctx: *mut *mut c_void, _flags: c_uint, dev: c_int) -> CUresult {
+    if pctx.is_null() || dev != 0 {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    *pctx = ptr::addr_of!(CONTEXT).cast_mut().cast();
+    CUDA_SUCCESS
+}
+
+/// Store `text` into `pstr`, unknown error codes (`None`) are reported like the real driver does.
+unsafe fn error_text(pstr: *mut *const c_char, text: Option<&'static CStr>) -> CUresult {
+    if pstr.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    match text {
+        Some(text) => {
+            *pstr = text.as_ptr();
+            CUDA_SUCCESS
+        }
+        None => {
+            *pstr = ptr::null();
+            CUDA_ERROR_INVALID_VALUE
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuCtxDestroy_v2(ctx: *mut c_void) -> CUresult {
+    if ctx != ptr::addr_of!(CONTEXT).cast_mut().cast() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuGetErrorName(error: CUresult, pstr: *mut *const c_char) -> CUresult {
+    error_text(pstr, ERRORS.iter().find(|entry| entry.0 == error).map(|entry| entry.1))
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuGetErrorString(error: CUresult, pstr: *mut *const c_char) -> CUresult {
+    error_text(pstr, ERRORS.iter().find(|entry| entry.0 == error).map(|entry| entry.2))
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuModuleLoadData(module: *mut *mut c_void, image: *const c_void) -> CUresult {
+    cuModuleLoadDataEx(module, image, 0, ptr::null_mut(), ptr::null_mut())
+}
+
+/// Copy `message` into a JIT log buffer of `size` bytes, truncating it if needed.
+unsafe fn write_log(buffer: *mut c_void, size: &mut usize, message: &str) {
+    if buffer.is_null() || *size == 0 {
+        *size = 0;
+        return;
+    }
+    let len = message.len().min(*size - 1);
+    ptr::copy_nonoverlapping(message.as_ptr(), buffer.cast::<u8>(), len);
+    *buffer.cast::<u8>().add(len) = 0;
+    *size = len + 1;
+}
+
+/// Parses the PTX module. The error log receives the parser error, the info log stays empty.
+#[no_mangle]
+pub unsafe extern "system" fn cuModuleLoadDataEx(
+    module: *mut *mut c_void,
+    image: *const c_void,
+    num_options: c_uint,
+    options: *mut c_uint,
+    option_values: *mut *mut c_void,
+) -> CUresult {
+    if module.is_null() || image.is_null() || (num_options > 0 && (options.is_null() || option_values.is_null())) {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    let (mut info_log, mut info_log_size) = (ptr::null_mut(), 0);
+    let (mut error_log, mut error_log_size) = (ptr::null_mut(), 0);
+    for i in 0..num_options as usize {
+        let value = *option_values.add(i);
+        match *options.add(i) {
+            CU_JIT_INFO_LOG_BUFFER => info_log = value,
+            CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES => info_log_size = value as usize,
+            CU_JIT_ERROR_LOG_BUFFER => error_log = value,
+            CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES => error_log_size = value as usize,
+            _ => {}
+        }
+    }
+    let source = CStr::from_ptr(image.cast()).to_string_lossy();
+    let result = ptx::parse(&source);
+    write_log(info_log, &mut info_log_size, "");
+    write_log(error_log, &mut error_log_size, result.as_ref().err().map_or("", String::as_str));
+    // Sizes are replaced with the number of bytes written, including the terminating NUL
+    for i in 0..num_options as usize {
+        match *options.add(i) {
+            CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES => *option_values.add(i) = info_log_size as *mut c_void,
+            CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES => *option_values.add(i) = error_log_size as *mut c_void,
+            _ => {}
+        }
+    }
+    match result {
+        Ok(kernels) => {
+            *module = Box::into_raw(Box::new(Module { kernels })).cast();
+            CUDA_SUCCESS
+        }
+        Err(_) => CUDA_ERROR_INVALID_PTX,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuModuleUnload(hmod: *mut c_void) -> CUresult {
+    if hmod.is_null() {
+        return CUDA_ERROR_INVALID_HANDLE;
+    }
+    drop(Box::from_raw(hmod.cast::<Module>()));
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuModuleGetFunction(
+    hfunc: *mut *mut c_void,
+    hmod: *mut c_void,
+    name: *const c_char,
+) -> CUresult {
+    if hfunc.is_null() || hmod.is_null() || name.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    let module = &*hmod.cast::<Module>();
+    let name = CStr::from_ptr(name).to_string_lossy();
+    match module.kernels.iter().find(|k| k.name == name) {
+        Some(kernel) => {
+            *hfunc = ptr::from_ref(kernel).cast_mut().cast();
+            CUDA_SUCCESS
+        }
+        None => CUDA_ERROR_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemGetInfo_v2(free: *mut usize, total: *mut usize) -> CUresult {
+    if free.is_null() || total.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    let allocated: usize = ALLOCATIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|allocations| allocations.values())
+        .map(Layout::size)
+        .sum();
+    *total = TOTAL_MEMORY;
+    *free = TOTAL_MEMORY.saturating_sub(allocated);
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemAlloc_v2(dptr: *mut CUdeviceptr, bytesize: usize) -> CUresult {
+    if dptr.is_null() || bytesize == 0 {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    match allocate(&ALLOCATIONS, bytesize) {
+        Some(address) => {
+            *dptr = address;
+            CUDA_SUCCESS
+        }
+        None => CUDA_ERROR_OUT_OF_MEMORY,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemFree_v2(dptr: CUdeviceptr) -> CUresult {
+    free(&ALLOCATIONS, dptr)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemHostAlloc(pp: *mut *mut c_void, bytesize: usize, _flags: c_uint) -> CUresult {
+    if pp.is_null() || bytesize == 0 {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    match allocate(&HOST_ALLOCATIONS, bytesize) {
+        Some(address) => {
+            *pp = address as *mut c_void;
+            CUDA_SUCCESS
+        }
+        None => CUDA_ERROR_OUT_OF_MEMORY,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemFreeHost(p: *mut c_void) -> CUresult {
+    free(&HOST_ALLOCATIONS, p as CUdeviceptr)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemcpyHtoD_v2(dst: CUdeviceptr, src: *const c_void, bytes: usize) -> CUresult {
+    ptr::copy_nonoverlapping(src.cast::<u8>(), dst as *mut u8, bytes);
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemcpyDtoH_v2(dst: *mut c_void, src: CUdeviceptr, bytes: usize) -> CUresult {
+    ptr::copy_nonoverlapping(src as *const u8, dst.cast::<u8>(), bytes);
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemcpyHtoDAsync_v2(
+    dst: CUdeviceptr,
+    src: *const c_void,
+    bytes: usize,
+    _stream: *mut c_void,
+) -> CUresult {
+    cuMemcpyHtoD_v2(dst, src, bytes)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuMemcpyDtoHAsync_v2(
+    dst: *mut c_void,
+    src: CUdeviceptr,
+    bytes: usize,
+    _stream: *mut c_void,
+) -> CUresult {
+    cuMemcpyDtoH_v2(dst, src, bytes)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuLaunchKernel(
+    f: *mut c_void,
+    grid_dim_x: c_uint,
+    grid_dim_y: c_uint,
+    grid_dim_z: c_uint,
+    block_dim_x: c_uint,
+    block_dim_y: c_uint,
+    block_dim_z: c_uint,
+    _shared_mem_bytes: c_uint,
+    _stream: *mut c_void,
+    kernel_params: *mut *mut c_void,
+    extra: *mut *mut c_void,
+) -> CUresult {
+    if f.is_null() || !extra.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    if grid_dim_y != 1 || grid_dim_z != 1 || block_dim_y != 1 || block_dim_z != 1 {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    let kernel = &*f.cast::<ptx::Kernel>();
+    if kernel.params > 0 && kernel_params.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    // All parameters of the harness kernels are 64-bit device pointers
+    let params: Vec<u64> = (0..kernel.params)
+        .map(|i| ptr::read_unaligned((*kernel_params.add(i)).cast::<u64>()))
+        .collect();
+    let dim = exec::Dim { grid: grid_dim_x, block: block_dim_x };
+    exec::launch(kernel, dim, &params);
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuStreamCreate(ph_stream: *mut *mut c_void, _flags: c_uint) -> CUresult {
+    if ph_stream.is_null() {
+        return CUDA_ERROR_INVALID_VALUE;
+    }
+    *ph_stream = ptr::addr_of!(STREAM).cast_mut().cast();
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuStreamDestroy_v2(_stream: *mut c_void) -> CUresult {
+    CUDA_SUCCESS
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn cuStreamSynchronize(_stream: *mut c_void) -> CUresult {
+    // Launches run to completion before returning
+    CUDA_SUCCESS
+}
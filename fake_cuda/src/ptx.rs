@@ -0,0 +1,794 @@
+//! Parser for the subset of PTX generated by the test harness.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Type {
+    B8,
+    B16,
+    B32,
+    B64,
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F16,
+    /// Two packed `f16` values
+    F16x2,
+    BF16,
+    /// Two packed `bf16` values
+    BF16x2,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(s: &str) -> Option<Type> {
+        Some(match s {
+            "b8" => Type::B8,
+            "b16" => Type::B16,
+            "b32" => Type::B32,
+            "b64" => Type::B64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "s8" => Type::S8,
+            "s16" => Type::S16,
+            "s32" => Type::S32,
+            "s64" => Type::S64,
+            "f16" => Type::F16,
+            "f16x2" => Type::F16x2,
+            "bf16" => Type::BF16,
+            "bf16x2" => Type::BF16x2,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            _ => return None,
+        })
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Type::B8 | Type::U8 | Type::S8 => 8,
+            Type::B16 | Type::U16 | Type::S16 | Type::F16 | Type::BF16 => 16,
+            Type::B32 | Type::U32 | Type::S32 | Type::F16x2 | Type::BF16x2 | Type::F32 => 32,
+            Type::B64 | Type::U64 | Type::S64 | Type::F64 => 64,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Type::F16 | Type::F16x2 | Type::BF16 | Type::BF16x2 | Type::F32 | Type::F64)
+    }
+
+    /// Type of both lanes of a packed type, `None` for all other types.
+    pub fn lane(self) -> Option<Type> {
+        match self {
+            Type::F16x2 => Some(Type::F16),
+            Type::BF16x2 => Some(Type::BF16),
+            _ => None,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, Type::S8 | Type::S16 | Type::S32 | Type::S64)
+    }
+
+    pub fn is_integer(self) -> bool {
+        !self.is_float()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    Default,
+    Rni,
+    Rzi,
+    Rmi,
+    Rpi,
+    Rn,
+    Rz,
+    Rm,
+    Rp,
+}
+
+impl Rounding {
+    fn parse(s: &str) -> Option<Rounding> {
+        Some(match s {
+            "rni" => Rounding::Rni,
+            "rzi" => Rounding::Rzi,
+            "rmi" => Rounding::Rmi,
+            "rpi" => Rounding::Rpi,
+            "rn" => Rounding::Rn,
+            "rz" => Rounding::Rz,
+            "rm" => Rounding::Rm,
+            "rp" => Rounding::Rp,
+            _ => return None,
+        })
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(self, Rounding::Rni | Rounding::Rzi | Rounding::Rmi | Rounding::Rpi)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Special {
+    TidX,
+    NtidX,
+    CtaidX,
+    NctaidX,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operand {
+    Reg(usize),
+    Imm(u64),
+    Special(Special),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MulMode {
+    Lo,
+    Hi,
+    Wide,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Function {
+    Rcp,
+    Sqrt,
+    Rsqrt,
+    Sin,
+    Cos,
+    Lg2,
+}
+
+/// Float arithmetic rounding its exact result once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+/// Comparison operator of `setp`, the `u` variants are also true for unordered operands.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Equ,
+    Neu,
+    Ltu,
+    Leu,
+    Gtu,
+    Geu,
+    Num,
+    Nan,
+}
+
+impl Compare {
+    fn parse(s: &str) -> Option<Compare> {
+        Some(match s {
+            "eq" => Compare::Eq,
+            "ne" => Compare::Ne,
+            "lt" => Compare::Lt,
+            "le" => Compare::Le,
+            "gt" => Compare::Gt,
+            "ge" => Compare::Ge,
+            "equ" => Compare::Equ,
+            "neu" => Compare::Neu,
+            "ltu" => Compare::Ltu,
+            "leu" => Compare::Leu,
+            "gtu" => Compare::Gtu,
+            "geu" => Compare::Geu,
+            "num" => Compare::Num,
+            "nan" => Compare::Nan,
+            _ => return None,
+        })
+    }
+
+    fn is_ordered_only(self) -> bool {
+        matches!(self, Compare::Eq | Compare::Ne | Compare::Lt | Compare::Le | Compare::Gt | Compare::Ge)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cvt {
+    pub to: Type,
+    pub from: Type,
+    pub rnd: Rounding,
+    pub ftz: bool,
+    pub sat: bool,
+    pub relu: bool,
+}
+
+#[derive(Debug)]
+pub enum Instruction {
+    Mov { ty: Type, dst: usize, src: Operand },
+    LdParam { dst: usize, param: usize },
+    Ld { ty: Type, dst: usize, addr: Operand, offset: i64 },
+    St { ty: Type, addr: Operand, offset: i64, src: Operand },
+    Cvt { cvt: Cvt, dst: usize, src: Operand },
+    Mul { mode: MulMode, ty: Type, dst: usize, a: Operand, b: Operand },
+    Mad { mode: MulMode, ty: Type, sat: bool, dst: usize, a: Operand, b: Operand, c: Operand },
+    Brev { ty: Type, dst: usize, src: Operand },
+    Bfe { ty: Type, dst: usize, a: Operand, b: Operand, c: Operand },
+    Bfi { ty: Type, dst: usize, a: Operand, b: Operand, c: Operand, d: Operand },
+    Shl { ty: Type, dst: usize, a: Operand, b: Operand },
+    Shr { ty: Type, dst: usize, a: Operand, b: Operand },
+    Div { ty: Type, dst: usize, a: Operand, b: Operand },
+    Rem { ty: Type, dst: usize, a: Operand, b: Operand },
+    /// Comparison writing its result into predicate `p` and, optionally, its negation into `q`
+    Setp { cmp: Compare, ty: Type, ftz: bool, p: usize, q: Option<usize>, a: Operand, b: Operand },
+    Selp { ty: Type, dst: usize, a: Operand, b: Operand, c: Operand },
+    MinMax { ty: Type, max: bool, ftz: bool, nan: bool, dst: usize, a: Operand, b: Operand },
+    /// Single-input float function, `rnd` is `None` for `.approx` variants
+    Unary { function: Function, rnd: Option<Rounding>, ftz: bool, dst: usize, src: Operand },
+    Arith { op: ArithOp, ty: Type, rnd: Rounding, ftz: bool, sat: bool, dst: usize, a: Operand, b: Operand },
+    Fma { ty: Type, rnd: Rounding, ftz: bool, sat: bool, relu: bool, dst: usize, a: Operand, b: Operand, c: Operand },
+    Ret,
+}
+
+pub struct Kernel {
+    pub name: String,
+    pub params: usize,
+    pub registers: usize,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Parse all `.entry` functions in the module.
+pub fn parse(source: &str) -> Result<Vec<Kernel>, String> {
+    let source: String = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut kernels = Vec::new();
+    let mut rest = source.as_str();
+    while let Some(start) = rest.find(".entry") {
+        rest = &rest[start + ".entry".len()..];
+        let open = rest.find('(').ok_or("missing parameter list")?;
+        let close = rest.find(')').ok_or("missing parameter list")?;
+        let name = rest[..open].trim().to_string();
+        let params: Vec<String> = rest[open + 1..close]
+            .split(',')
+            .map(|p| p.split_whitespace().last().unwrap_or_default().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        rest = &rest[close + 1..];
+
+        let body_start = rest.find('{').ok_or("missing kernel body")?;
+        let body_end = rest.find('}').ok_or("missing kernel body")?;
+        let body = &rest[body_start + 1..body_end];
+        if body.contains('{') {
+            return Err("nested blocks are not supported".to_string());
+        }
+        rest = &rest[body_end + 1..];
+
+        let mut parser = Parser { registers: HashMap::new(), params };
+        let instructions = body
+            .split(';')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| parser.statement(s).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        kernels.push(Kernel {
+            name,
+            params: parser.params.len(),
+            registers: parser.registers.len(),
+            instructions,
+        });
+    }
+    if kernels.is_empty() {
+        return Err("module contains no kernels".to_string());
+    }
+    Ok(kernels)
+}
+
+struct Parser {
+    registers: HashMap<String, usize>,
+    params: Vec<String>,
+}
+
+impl Parser {
+    fn statement(&mut self, statement: &str) -> Result<Option<Instruction>, String> {
+        let (opcode, operands) = match statement.split_once(char::is_whitespace) {
+            Some((opcode, operands)) => (opcode, operands.trim()),
+            None => (statement, ""),
+        };
+        if opcode == ".reg" {
+            self.declare(operands)?;
+            return Ok(None);
+        }
+        if opcode.starts_with('.') {
+            return Err(format!("unsupported directive '{opcode}'"));
+        }
+        if opcode.starts_with('@') {
+            return Err("predicated instructions are not supported".to_string());
+        }
+        self.instruction(opcode, operands)
+            .map(Some)
+            .map_err(|e| format!("{e} in '{statement}'"))
+    }
+
+    fn declare(&mut self, declaration: &str) -> Result<(), String> {
+        let (ty, names) = declaration
+            .split_once(char::is_whitespace)
+            .ok_or("malformed register declaration")?;
+        // Predicates are stored in ordinary registers as 0 or 1
+        ty.strip_prefix('.')
+            .filter(|ty| *ty == "pred")
+            .map(|_| Type::U8)
+            .or_else(|| ty.strip_prefix('.').and_then(Type::parse))
+            .ok_or_else(|| format!("unknown register type '{ty}'"))?;
+        for name in names.split(',').map(str::trim) {
+            match name.split_once('<') {
+                Some((prefix, count)) => {
+                    let count: usize = count
+                        .trim_end_matches('>')
+                        .parse()
+                        .map_err(|_| format!("malformed register range '{name}'"))?;
+                    for i in 0..count {
+                        self.new_register(format!("{prefix}{i}"));
+                    }
+                }
+                None => self.new_register(name.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn new_register(&mut self, name: String) {
+        let index = self.registers.len();
+        self.registers.entry(name).or_insert(index);
+    }
+
+    fn register(&self, name: &str) -> Result<usize, String> {
+        self.registers
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("undeclared register '{name}'"))
+    }
+
+    fn operand(&self, text: &str) -> Result<Operand, String> {
+        let special = match text {
+            "%tid.x" => Some(Special::TidX),
+            "%ntid.x" => Some(Special::NtidX),
+            "%ctaid.x" => Some(Special::CtaidX),
+            "%nctaid.x" => Some(Special::NctaidX),
+            _ => None,
+        };
+        if let Some(special) = special {
+            return Ok(Operand::Special(special));
+        }
+        if let Some(value) = parse_immediate(text) {
+            return Ok(Operand::Imm(value));
+        }
+        self.register(text).map(Operand::Reg)
+    }
+
+    fn address(&self, text: &str) -> Result<(Operand, i64), String> {
+        let inner = text
+            .strip_prefix('[')
+            .and_then(|t| t.strip_suffix(']'))
+            .ok_or_else(|| format!("malformed address '{text}'"))?
+            .trim();
+        match inner.split_once('+') {
+            Some((base, offset)) => {
+                let offset = parse_immediate(offset.trim())
+                    .ok_or_else(|| format!("malformed offset in '{text}'"))?;
+                Ok((self.operand(base.trim())?, offset as i64))
+            }
+            None => Ok((self.operand(inner)?, 0)),
+        }
+    }
+
+    fn instruction(&self, opcode: &str, operands: &str) -> Result<Instruction, String> {
+        let mut parts = opcode.split('.');
+        let name = parts.next().unwrap_or_default();
+        let modifiers: Vec<&str> = parts.collect();
+        let operands: Vec<&str> = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands.split(',').map(str::trim).collect()
+        };
+        let types: Vec<Type> = modifiers.iter().filter_map(|m| Type::parse(m)).collect();
+        let flags: Vec<&str> = modifiers
+            .iter()
+            .copied()
+            .filter(|m| Type::parse(m).is_none())
+            .collect();
+        let has = |flag: &str| flags.contains(&flag);
+        let ty = |index: usize| {
+            types
+                .get(index)
+                .copied()
+                .ok_or_else(|| "missing instruction type".to_string())
+        };
+        let arity = |count: usize| {
+            if operands.len() == count {
+                Ok(())
+            } else {
+                Err(format!("expected {count} operands, got {}", operands.len()))
+            }
+        };
+        let rounding = flags.iter().find_map(|f| Rounding::parse(f));
+        let check_flags = |allowed: &[&str]| {
+            for flag in &flags {
+                if !allowed.contains(flag) {
+                    return Err(format!("unsupported modifier '.{flag}'"));
+                }
+            }
+            Ok(())
+        };
+
+        Ok(match name {
+            "ret" => {
+                arity(0)?;
+                Instruction::Ret
+            }
+            "mov" => {
+                arity(2)?;
+                check_flags(&[])?;
+                Instruction::Mov {
+                    ty: ty(0)?,
+                    dst: self.register(operands[0])?,
+                    src: self.operand(operands[1])?,
+                }
+            }
+            "ld" if has("param") => {
+                arity(2)?;
+                let param = operands[1]
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .trim();
+                let param = self
+                    .params
+                    .iter()
+                    .position(|p| p == param)
+                    .ok_or_else(|| format!("unknown parameter '{param}'"))?;
+                Instruction::LdParam { dst: self.register(operands[0])?, param }
+            }
+            "ld" => {
+                arity(2)?;
+                check_flags(&["global"])?;
+                let (addr, offset) = self.address(operands[1])?;
+                Instruction::Ld { ty: ty(0)?, dst: self.register(operands[0])?, addr, offset }
+            }
+            "st" => {
+                arity(2)?;
+                check_flags(&["global"])?;
+                let (addr, offset) = self.address(operands[0])?;
+                Instruction::St { ty: ty(0)?, addr, offset, src: self.operand(operands[1])? }
+            }
+            "cvt" => {
+                arity(2)?;
+                check_flags(&["rni", "rzi", "rmi", "rpi", "rn", "rz", "rm", "rp", "ftz", "sat", "relu"])?;
+                let cvt = Cvt {
+                    to: ty(0)?,
+                    from: ty(1)?,
+                    rnd: rounding.unwrap_or(Rounding::Default),
+                    ftz: has("ftz"),
+                    sat: has("sat"),
+                    relu: has("relu"),
+                };
+                validate_cvt(&cvt)?;
+                Instruction::Cvt { cvt, dst: self.register(operands[0])?, src: self.operand(operands[1])? }
+            }
+            "add" | "sub" | "mul" if types.first().is_some_and(|ty| ty.is_float()) => {
+                arity(3)?;
+                check_flags(&["rn", "rz", "rm", "rp", "ftz", "sat"])?;
+                let ty = ty(0)?;
+                validate_float_arith(ty, rounding, has("ftz") || has("sat"))?;
+                let op = match name {
+                    "add" => ArithOp::Add,
+                    "sub" => ArithOp::Sub,
+                    _ => ArithOp::Mul,
+                };
+                Instruction::Arith {
+                    op,
+                    ty,
+                    rnd: rounding.unwrap_or(Rounding::Rn),
+                    ftz: has("ftz"),
+                    sat: has("sat"),
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                }
+            }
+            "fma" => {
+                arity(4)?;
+                check_flags(&["rn", "rz", "rm", "rp", "ftz", "sat", "relu"])?;
+                let ty = ty(0)?;
+                let rnd = rounding.ok_or("fma requires a rounding modifier")?;
+                validate_float_arith(ty, Some(rnd), has("ftz") || has("sat"))?;
+                if has("relu") && (has("sat") || !matches!(ty.lane().unwrap_or(ty), Type::F16 | Type::BF16)) {
+                    return Err(".relu is only supported for half precision without .sat".to_string());
+                }
+                Instruction::Fma {
+                    ty,
+                    rnd,
+                    ftz: has("ftz"),
+                    sat: has("sat"),
+                    relu: has("relu"),
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                    c: self.operand(operands[3])?,
+                }
+            }
+            "mul" | "mad" => {
+                check_flags(&["lo", "hi", "wide", "sat"])?;
+                let ty = ty(0)?;
+                if ty.is_float() {
+                    return Err("floating point mad is not supported".to_string());
+                }
+                let mode = if has("lo") {
+                    MulMode::Lo
+                } else if has("hi") {
+                    MulMode::Hi
+                } else if has("wide") {
+                    MulMode::Wide
+                } else {
+                    return Err("integer multiplication requires .lo, .hi or .wide".to_string());
+                };
+                if mode == MulMode::Wide && ty.bits() == 64 {
+                    return Err(".wide is not supported for 64-bit types".to_string());
+                }
+                if name == "mul" {
+                    arity(3)?;
+                    Instruction::Mul {
+                        mode,
+                        ty,
+                        dst: self.register(operands[0])?,
+                        a: self.operand(operands[1])?,
+                        b: self.operand(operands[2])?,
+                    }
+                } else {
+                    arity(4)?;
+                    let sat = has("sat");
+                    if sat && (mode != MulMode::Hi || ty != Type::S32) {
+                        return Err(".sat is only supported for mad.hi.s32".to_string());
+                    }
+                    Instruction::Mad {
+                        mode,
+                        ty,
+                        sat,
+                        dst: self.register(operands[0])?,
+                        a: self.operand(operands[1])?,
+                        b: self.operand(operands[2])?,
+                        c: self.operand(operands[3])?,
+                    }
+                }
+            }
+            "brev" => {
+                arity(2)?;
+                check_flags(&[])?;
+                Instruction::Brev { ty: ty(0)?, dst: self.register(operands[0])?, src: self.operand(operands[1])? }
+            }
+            "bfe" => {
+                arity(4)?;
+                check_flags(&[])?;
+                Instruction::Bfe {
+                    ty: ty(0)?,
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                    c: self.operand(operands[3])?,
+                }
+            }
+            "bfi" => {
+                arity(5)?;
+                check_flags(&[])?;
+                Instruction::Bfi {
+                    ty: ty(0)?,
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                    c: self.operand(operands[3])?,
+                    d: self.operand(operands[4])?,
+                }
+            }
+            "shl" | "shr" => {
+                arity(3)?;
+                check_flags(&[])?;
+                let ty = ty(0)?;
+                let dst = self.register(operands[0])?;
+                let a = self.operand(operands[1])?;
+                let b = self.operand(operands[2])?;
+                if name == "shl" {
+                    Instruction::Shl { ty, dst, a, b }
+                } else {
+                    Instruction::Shr { ty, dst, a, b }
+                }
+            }
+            "div" | "rem" => {
+                arity(3)?;
+                check_flags(&[])?;
+                let ty = ty(0)?;
+                if ty.is_float() {
+                    return Err("floating point division is not supported".to_string());
+                }
+                let dst = self.register(operands[0])?;
+                let a = self.operand(operands[1])?;
+                let b = self.operand(operands[2])?;
+                if name == "div" {
+                    Instruction::Div { ty, dst, a, b }
+                } else {
+                    Instruction::Rem { ty, dst, a, b }
+                }
+            }
+            "setp" => {
+                arity(3)?;
+                let cmp = flags
+                    .first()
+                    .and_then(|f| Compare::parse(f))
+                    .ok_or("setp requires a comparison operator")?;
+                check_flags(&[flags[0], "ftz"])?;
+                let ty = ty(0)?;
+                if ty.is_integer() && !cmp.is_ordered_only() {
+                    return Err("unordered comparisons require a floating point type".to_string());
+                }
+                let (p, q) = match operands[0].split_once('|') {
+                    Some((p, q)) => (self.register(p.trim())?, Some(self.register(q.trim())?)),
+                    None => (self.register(operands[0])?, None),
+                };
+                Instruction::Setp {
+                    cmp,
+                    ty,
+                    ftz: has("ftz"),
+                    p,
+                    q,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                }
+            }
+            "selp" => {
+                arity(4)?;
+                check_flags(&[])?;
+                Instruction::Selp {
+                    ty: ty(0)?,
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                    c: self.operand(operands[3])?,
+                }
+            }
+            "min" | "max" => {
+                arity(3)?;
+                check_flags(&["ftz", "NaN"])?;
+                let ty = ty(0)?;
+                match ty.lane().unwrap_or(ty) {
+                    Type::F16 => {}
+                    Type::BF16 if !has("ftz") => {}
+                    _ => return Err("only .f16 and .bf16 min/max are supported".to_string()),
+                }
+                Instruction::MinMax {
+                    ty,
+                    max: name == "max",
+                    ftz: has("ftz"),
+                    nan: has("NaN"),
+                    dst: self.register(operands[0])?,
+                    a: self.operand(operands[1])?,
+                    b: self.operand(operands[2])?,
+                }
+            }
+            "rcp" | "sqrt" | "rsqrt" | "sin" | "cos" | "lg2" => {
+                arity(2)?;
+                check_flags(&["approx", "rn", "rz", "rm", "rp", "ftz"])?;
+                if ty(0)? != Type::F32 {
+                    return Err("only .f32 is supported".to_string());
+                }
+                let function = match name {
+                    "rcp" => Function::Rcp,
+                    "sqrt" => Function::Sqrt,
+                    "rsqrt" => Function::Rsqrt,
+                    "sin" => Function::Sin,
+                    "cos" => Function::Cos,
+                    _ => Function::Lg2,
+                };
+                let approx_only = matches!(function, Function::Rsqrt | Function::Sin | Function::Cos | Function::Lg2);
+                let rnd = match (has("approx"), rounding) {
+                    (true, None) => None,
+                    (false, Some(rnd)) if !approx_only => Some(rnd),
+                    _ => return Err("invalid rounding modifiers".to_string()),
+                };
+                Instruction::Unary {
+                    function,
+                    rnd,
+                    ftz: has("ftz"),
+                    dst: self.register(operands[0])?,
+                    src: self.operand(operands[1])?,
+                }
+            }
+            _ => return Err(format!("unsupported instruction '{name}'")),
+        })
+    }
+}
+
+fn parse_immediate(text: &str) -> Option<u64> {
+    if let Some(bits) = text.strip_prefix("0f").or_else(|| text.strip_prefix("0F")) {
+        return u32::from_str_radix(bits, 16).ok().map(u64::from);
+    }
+    if let Some(bits) = text.strip_prefix("0d").or_else(|| text.strip_prefix("0D")) {
+        return u64::from_str_radix(bits, 16).ok();
+    }
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u64::from_str_radix(hex.trim_end_matches('U'), 16).ok();
+    }
+    if let Some(negative) = text.strip_prefix('-') {
+        return negative.parse::<i64>().ok().map(|v| v.wrapping_neg() as u64);
+    }
+    text.trim_end_matches('U').parse::<u64>().ok()
+}
+
+/// Reject types and modifiers of float `add`, `sub`, `mul` and `fma` that the PTX ISA does not allow.
+fn validate_float_arith(ty: Type, rnd: Option<Rounding>, ftz_or_sat: bool) -> Result<(), String> {
+    match ty {
+        Type::F16 | Type::F16x2 | Type::BF16 | Type::BF16x2 if rnd.is_some_and(|rnd| rnd != Rounding::Rn) => {
+            Err("half precision only supports .rn".to_string())
+        }
+        Type::BF16 | Type::BF16x2 if ftz_or_sat => Err(".ftz and .sat are not supported for .bf16".to_string()),
+        Type::F64 if ftz_or_sat => Err(".ftz and .sat are not supported for .f64".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Reject `cvt` modifier combinations that the PTX ISA does not allow.
+fn validate_cvt(cvt: &Cvt) -> Result<(), String> {
+    let Cvt { to, from, rnd, ftz, sat, relu } = *cvt;
+    let invalid = Err("invalid cvt modifiers".to_string());
+    // bf16 only converts from f32 rounding to nearest and to f32 exactly
+    if (to == Type::BF16 || from == Type::BF16)
+        && !matches!((to, from, rnd), (Type::BF16, Type::F32, Rounding::Rn) | (Type::F32, Type::BF16, Rounding::Default))
+    {
+        return invalid;
+    }
+    if relu && !(from == Type::F32 && matches!(to, Type::F16 | Type::BF16) && rnd == Rounding::Rn && !ftz && !sat) {
+        return invalid;
+    }
+    if sat && to.is_integer() && from.is_integer() {
+        let widening = to.bits() >= from.bits();
+        if from.is_signed() && to.is_signed() && widening {
+            return invalid;
+        }
+        if !from.is_signed() && !to.is_signed() && widening {
+            return invalid;
+        }
+        if !from.is_signed() && to.is_signed() && to.bits() > from.bits() {
+            return invalid;
+        }
+    }
+    if ftz && to != Type::F32 && from != Type::F32 {
+        return invalid;
+    }
+    let float_narrowing = to.is_float() && from.is_float() && to.bits() < from.bits();
+    let float_same_size = to.is_float() && from.is_float() && to.bits() == from.bits();
+    match rnd {
+        Rounding::Default => {
+            if float_narrowing || to.is_float() != from.is_float() {
+                return invalid;
+            }
+        }
+        rnd if rnd.is_integer() => {
+            if !(from.is_float() && to.is_integer() || float_same_size) {
+                return invalid;
+            }
+        }
+        _ => {
+            if !(from.is_integer() && to.is_float() || float_narrowing) {
+                return invalid;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Checkpoints of long runs, so that a run interrupted by e.g. a reboot or a driver reset can be
+//! continued with `--resume` instead of starting over from the first test.
+//!
+//! A checkpoint is a text file with one entry per finished test, appended as soon as the test
+//! finishes:
+//!
+//! ```text
+//! Test    name duration
+//! ...     result of the test, in the format of results of isolated tests, see `isolate`
+//! End
+//! ```
+//!
+//! An entry without its `End` line, left behind by a run killed while writing it, is ignored and
+//! its test runs again. Tests that ran out of time, including those skipped because the budget
+//! ran out, get no entry at all, see `run_each`.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{isolate, report::TestResult};
+
+/// Checkpoint file of the current run.
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint at `path` holding `results` of tests finished by an interrupted run.
+    pub fn create(path: &Path, results: &[TestResult]) -> io::Result<Self> {
+        fs::write(path, results.iter().map(entry).collect::<String>())?;
+        Ok(Checkpoint { path: path.to_path_buf() })
+    }
+
+    /// Add the result of a finished test, it is on disk when this returns.
+    pub fn append(&self, result: &TestResult) -> io::Result<()> {
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(entry(result).as_bytes())?;
+        file.sync_data()
+    }
+}
+
+fn entry(result: &TestResult) -> String {
+    let duration = result.duration.as_secs_f64();
+    format!("Test\t{}\t{duration}\n{}End\n", result.name, isolate::encode(&result.result))
+}
+
+/// Results of all tests recorded into the checkpoint at `path`, in the order they finished.
+pub fn read(path: &Path) -> Result<Vec<TestResult>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut results = Vec::new();
+    let mut lines = text.lines();
+    while let Some(header) = lines.next() {
+        let mut record = String::new();
+        let mut finished = false;
+        for line in lines.by_ref() {
+            if line == "End" {
+                finished = true;
+                break;
+            }
+            record.push_str(line);
+            record.push('\n');
+        }
+        if !finished {
+            break;
+        }
+        let error = || format!("{}: invalid entry '{header}'", path.display());
+        let (name, duration) = match header.split('\t').collect::<Vec<_>>()[..] {
+            ["Test", name, duration] => (name, duration),
+            _ => return Err(error()),
+        };
+        let duration = duration.parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()).ok_or_else(error)?;
+        let result = isolate::decode(&record).ok_or_else(error)?;
+        results.push(TestResult { name: name.to_string(), result, duration });
+    }
+    Ok(results)
+}
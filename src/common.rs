@@ -61,6 +61,7 @@ impl Rounding {
         }
     }
 
+    /// Run `f` with this rounding mode. Only affects the calling thread.
     pub fn with<T>(&self, f: impl FnOnce() -> T) -> T {
         let old = unsafe { llvm_get_rounding() };
         unsafe { llvm_set_rounding(self.as_llvm()) };
@@ -98,6 +99,17 @@ where
     }
 }
 
+/// Clamp to the range [+0.0, 1.0] like the `.sat` modifier of float instructions, NaN gives +0.0.
+pub fn saturate<T: Float>(x: T) -> T {
+    if x.is_nan() || x <= T::zero() {
+        T::zero()
+    } else if x > T::one() {
+        T::one()
+    } else {
+        x
+    }
+}
+
 pub fn flush_to_zero_f16<T: Float + Copy + 'static>(x: &mut T, ftz: bool)
 where
     half::f16: AsPrimitive<T>,
@@ -111,3 +123,20 @@ where
         *x = T::zero()
     }
 }
+
+/// Both 16-bit halves of `input` as values of the 16-bit type `T`, the lower one first. Splits
+/// inputs of exhaustive tests into two operands.
+pub fn halves<T: Copy>(input: u32) -> (T, T) {
+    assert_eq!(mem::size_of::<T>(), 2);
+    let (low, high) = (input as u16, (input >> 16) as u16);
+    unsafe { (mem::transmute_copy(&low), mem::transmute_copy(&high)) }
+}
+
+/// Prefix every line of `source` with its number, for printing programs that failed compilation.
+pub fn numbered_lines(source: &str) -> String {
+    let mut text = String::new();
+    for (i, line) in source.lines().enumerate() {
+        text.push_str(&format!("{:3} | {}\n", i + 1, line));
+    }
+    text
+}
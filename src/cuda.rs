@@ -1,8 +1,6 @@
-use std::num::NonZeroU32;
+use std::{ffi::{c_void, CStr}, fmt, marker::PhantomData, num::NonZeroU32, ptr};
 
-use libloading::Library;
-
-use crate::impl_library;
+use crate::{impl_library, test::OnDevice};
 
 
 #[repr(C)]
@@ -37,67 +35,371 @@ pub type CUdeviceptr = ::std::os::raw::c_ulonglong;
 
 pub type CUdevice = ::std::os::raw::c_int;
 
+pub const CU_STREAM_NON_BLOCKING: ::std::os::raw::c_uint = 1;
+
+#[allow(non_camel_case_types)]
+pub type CUjit_option = ::std::os::raw::c_uint;
+pub const CU_JIT_INFO_LOG_BUFFER: CUjit_option = 3;
+pub const CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES: CUjit_option = 4;
+pub const CU_JIT_ERROR_LOG_BUFFER: CUjit_option = 5;
+pub const CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES: CUjit_option = 6;
+
 pub type CUresult = Result<(), NonZeroU32>;
 static_assertions::assert_eq_size!(CUresult, u32);
 
-pub struct Cuda {
-    library: Library,
+impl Cuda {
+    /// Name and description of an error code, e.g. `CUDA_ERROR_INVALID_PTX (218): a PTX JIT compilation failed`.
+    pub fn error_text(&self, code: NonZeroU32) -> String {
+        let mut name = ptr::null();
+        let mut description = ptr::null();
+        let name_result = unsafe { self.cuGetErrorName(code.get(), &mut name) };
+        let description_result = unsafe { self.cuGetErrorString(code.get(), &mut description) };
+        match name_result.and(description_result) {
+            Ok(()) if !name.is_null() && !description.is_null() => {
+                let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+                let description = unsafe { CStr::from_ptr(description) }.to_string_lossy();
+                format!("{name} ({code}): {description}")
+            }
+            _ => format!("unknown error ({code})"),
+        }
+    }
+
+    /// Turn the result of a driver call into a typed error.
+    pub fn check(&self, result: CUresult) -> Result<(), CudaError> {
+        result.map_err(|code| CudaError { code, text: self.error_text(code) })
+    }
+
+    /// Free and total device memory, in bytes.
+    pub fn mem_info(&self) -> Result<(usize, usize), CudaError> {
+        let mut free = 0;
+        let mut total = 0;
+        self.check(unsafe { self.cuMemGetInfo_v2(&mut free, &mut total) })?;
+        Ok((free, total))
+    }
 }
 
-impl Cuda {
-    pub fn new(path: String) -> Self {
-        let library = unsafe { Library::new(path).unwrap() };
-        Self { library }
+impl_library! {
+    pub struct Cuda;
+    "system" fn cuInit(Flags: ::std::os::raw::c_uint) -> CUresult;
+    "system" fn cuCtxCreate_v2(
+        pctx: *mut CUcontext,
+        flags: ::std::os::raw::c_uint,
+        dev: CUdevice,
+    ) -> CUresult;
+    "system" fn cuGetErrorName(
+        error: ::std::os::raw::c_uint,
+        pStr: *mut *const ::std::os::raw::c_char,
+    ) -> CUresult;
+    "system" fn cuGetErrorString(
+        error: ::std::os::raw::c_uint,
+        pStr: *mut *const ::std::os::raw::c_char,
+    ) -> CUresult;
+    "system" fn cuModuleLoadDataEx(
+        module: *mut CUmodule,
+        image: *const ::std::os::raw::c_void,
+        numOptions: ::std::os::raw::c_uint,
+        options: *mut CUjit_option,
+        optionValues: *mut *mut ::std::os::raw::c_void,
+    ) -> CUresult;
+    "system" fn cuModuleGetFunction(
+        hfunc: *mut CUfunction,
+        hmod: CUmodule,
+        name: *const ::std::os::raw::c_char,
+    ) -> CUresult;
+    "system" fn cuMemAlloc_v2(dptr: *mut CUdeviceptr, bytesize: usize) -> CUresult;
+    "system" fn cuMemcpyHtoDAsync_v2(
+        dstDevice: CUdeviceptr,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) -> CUresult;
+    //"system" fn cuMemsetD8_v2(dstDevice: CUdeviceptr, uc: ::std::os::raw::c_uchar, N: usize)
+    //    -> CUresult;
+    "system" fn cuLaunchKernel(
+        f: CUfunction,
+        gridDimX: ::std::os::raw::c_uint,
+        gridDimY: ::std::os::raw::c_uint,
+        gridDimZ: ::std::os::raw::c_uint,
+        blockDimX: ::std::os::raw::c_uint,
+        blockDimY: ::std::os::raw::c_uint,
+        blockDimZ: ::std::os::raw::c_uint,
+        sharedMemBytes: ::std::os::raw::c_uint,
+        hStream: CUstream,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+        extra: *mut *mut ::std::os::raw::c_void,
+    ) -> CUresult;
+    "system" fn cuMemcpyDtoHAsync_v2(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) -> CUresult;
+    "system" fn cuMemHostAlloc(
+        pp: *mut *mut ::std::os::raw::c_void,
+        bytesize: usize,
+        Flags: ::std::os::raw::c_uint,
+    ) -> CUresult;
+    "system" fn cuMemFreeHost(p: *mut ::std::os::raw::c_void) -> CUresult;
+    "system" fn cuStreamCreate(phStream: *mut CUstream, Flags: ::std::os::raw::c_uint) -> CUresult;
+    "system" fn cuStreamDestroy_v2(hStream: CUstream) -> CUresult;
+    "system" fn cuStreamSynchronize(hStream: CUstream) -> CUresult;
+    "system" fn cuMemFree_v2(dptr: CUdeviceptr) -> CUresult;
+    "system" fn cuModuleUnload(hmod: CUmodule) -> CUresult;
+    "system" fn cuCtxDestroy_v2(ctx: CUcontext) -> CUresult;
+    "system" fn cuMemGetInfo_v2(free: *mut usize, total: *mut usize) -> CUresult;
+}
+
+/// Error reported by a CUDA driver call.
+#[derive(Debug, Clone)]
+pub struct CudaError {
+    pub code: NonZeroU32,
+    /// Name and description of the error, see `Cuda::error_text`
+    pub text: String,
+}
+
+impl fmt::Display for CudaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
     }
 }
 
-impl Cuda {
-    impl_library! {
-        "system" fn cuInit(Flags: ::std::os::raw::c_uint) -> CUresult;
-        "system" fn cuCtxCreate_v2(
-            pctx: *mut CUcontext,
-            flags: ::std::os::raw::c_uint,
-            dev: CUdevice,
-        ) -> CUresult;
-        "system" fn cuModuleLoadData(
-            module: *mut CUmodule,
-            image: *const ::std::os::raw::c_void,
-        ) -> CUresult;
-        "system" fn cuModuleGetFunction(
-            hfunc: *mut CUfunction,
-            hmod: CUmodule,
-            name: *const ::std::os::raw::c_char,
-        ) -> CUresult;
-        "system" fn cuMemAlloc_v2(dptr: *mut CUdeviceptr, bytesize: usize) -> CUresult;
-        "system" fn cuMemcpyHtoD_v2(
-            dstDevice: CUdeviceptr,
-            srcHost: *const ::std::os::raw::c_void,
-            ByteCount: usize,
-        ) -> CUresult;
-        //"system" fn cuMemsetD8_v2(dstDevice: CUdeviceptr, uc: ::std::os::raw::c_uchar, N: usize)
-        //    -> CUresult;
-        "system" fn cuLaunchKernel(
-            f: CUfunction,
-            gridDimX: ::std::os::raw::c_uint,
-            gridDimY: ::std::os::raw::c_uint,
-            gridDimZ: ::std::os::raw::c_uint,
-            blockDimX: ::std::os::raw::c_uint,
-            blockDimY: ::std::os::raw::c_uint,
-            blockDimZ: ::std::os::raw::c_uint,
-            sharedMemBytes: ::std::os::raw::c_uint,
-            hStream: CUstream,
-            kernelParams: *mut *mut ::std::os::raw::c_void,
-            extra: *mut *mut ::std::os::raw::c_void,
-        ) -> CUresult;
-        "system" fn cuMemcpyDtoH_v2(
-            dstHost: *mut ::std::os::raw::c_void,
-            srcDevice: CUdeviceptr,
-            ByteCount: usize,
-        ) -> CUresult;
-        "system" fn cuStreamSynchronize(hStream: CUstream) -> CUresult;
-        "system" fn cuMemFree_v2(dptr: CUdeviceptr) -> CUresult;
-        "system" fn cuModuleUnload(hmod: CUmodule) -> CUresult;
-        //"system" fn cuCtxDestroy_v2(ctx: CUcontext) -> CUresult;
-        "system" fn cuMemGetInfo_v2(free: *mut usize, total: *mut usize) -> CUresult;
+/// Context on the first device, current on the calling thread until dropped.
+pub struct Context<'a> {
+    cuda: &'a Cuda,
+    context: CUcontext,
+}
+
+impl<'a> Context<'a> {
+    pub fn create(cuda: &'a Cuda) -> Result<Self, CudaError> {
+        cuda.check(unsafe { cuda.cuInit(0) })?;
+        let mut context = ptr::null_mut();
+        cuda.check(unsafe { cuda.cuCtxCreate_v2(&mut context, 0, 0) })?;
+        Ok(Context { cuda, context })
+    }
+}
+
+impl Drop for Context<'_> {
+    fn drop(&mut self) {
+        // Errors can't be reported from drop, there is nothing to do about them anyway
+        let _ = unsafe { self.cuda.cuCtxDestroy_v2(self.context) };
+    }
+}
+
+/// Module that failed to load, with the logs produced by the JIT compiler.
+pub struct LoadError {
+    pub error: CudaError,
+    pub log: String,
+}
+
+/// Size of each of the JIT compiler log buffers, longer logs are truncated
+const JIT_LOG_SIZE: usize = 1 << 16;
+
+/// Loaded module, unloaded on drop.
+pub struct Module<'a> {
+    cuda: &'a Cuda,
+    module: CUmodule,
+}
+
+impl<'a> Module<'a> {
+    /// Load a module from PTX source, capturing the JIT compiler's error and info logs.
+    pub fn load(cuda: &'a Cuda, image: &CStr) -> Result<Self, LoadError> {
+        let mut error_log = vec![0u8; JIT_LOG_SIZE];
+        let mut info_log = vec![0u8; JIT_LOG_SIZE];
+        let mut options: [CUjit_option; 4] = [
+            CU_JIT_ERROR_LOG_BUFFER,
+            CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES,
+            CU_JIT_INFO_LOG_BUFFER,
+            CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES,
+        ];
+        let mut values: [*mut c_void; 4] = [
+            error_log.as_mut_ptr().cast(),
+            JIT_LOG_SIZE as _,
+            info_log.as_mut_ptr().cast(),
+            JIT_LOG_SIZE as _,
+        ];
+
+        let mut module = ptr::null_mut();
+        let result = unsafe {
+            cuda.cuModuleLoadDataEx(
+                &mut module,
+                image.as_ptr() as _,
+                options.len() as _,
+                options.as_mut_ptr(),
+                values.as_mut_ptr(),
+            )
+        };
+
+        match cuda.check(result) {
+            Ok(()) => Ok(Module { cuda, module }),
+            Err(error) => {
+                let log = |buffer: &[u8]| {
+                    let text = CStr::from_bytes_until_nul(buffer).map_or_else(|_| String::from_utf8_lossy(buffer), CStr::to_string_lossy);
+                    text.trim_end().to_string()
+                };
+                let logs: Vec<_> = [log(&error_log), log(&info_log)].into_iter().filter(|log| !log.is_empty()).collect();
+                Err(LoadError { error, log: logs.join("\n") })
+            }
+        }
+    }
+
+    pub fn function(&self, name: &CStr) -> Result<Function<'_>, CudaError> {
+        let mut function = ptr::null_mut();
+        self.cuda.check(unsafe { self.cuda.cuModuleGetFunction(&mut function, self.module, name.as_ptr()) })?;
+        Ok(Function { cuda: self.cuda, function, _module: PhantomData })
+    }
+}
+
+impl Drop for Module<'_> {
+    fn drop(&mut self) {
+        let _ = unsafe { self.cuda.cuModuleUnload(self.module) };
+    }
+}
+
+/// Kernel of a loaded module, can't outlive it.
+pub struct Function<'m> {
+    cuda: &'m Cuda,
+    function: CUfunction,
+    _module: PhantomData<&'m Module<'m>>,
+}
+
+impl Function<'_> {
+    /// Queue a one-dimensional launch of `grid` blocks of `block` threads on `stream`.
+    ///
+    /// # Safety
+    /// `params` must match the kernel's parameters and the kernel may only access memory within
+    /// buffers they point to, which must stay alive until the stream is synchronized.
+    pub unsafe fn launch(&self, stream: &Stream, grid: u32, block: u32, params: &[CUdeviceptr]) -> Result<(), CudaError> {
+        let mut args: Vec<_> = params.iter().map(|param| param as *const CUdeviceptr as *mut c_void).collect();
+        self.cuda.check(self.cuda.cuLaunchKernel(
+            self.function,
+            grid,
+            1,
+            1,
+            block,
+            1,
+            1,
+            0,
+            stream.stream,
+            args.as_mut_ptr(),
+            ptr::null_mut(),
+        ))
+    }
+}
+
+/// Non-blocking stream, destroyed on drop.
+pub struct Stream<'a> {
+    cuda: &'a Cuda,
+    stream: CUstream,
+}
+
+impl<'a> Stream<'a> {
+    pub fn create(cuda: &'a Cuda) -> Result<Self, CudaError> {
+        let mut stream = ptr::null_mut();
+        cuda.check(unsafe { cuda.cuStreamCreate(&mut stream, CU_STREAM_NON_BLOCKING) })?;
+        Ok(Stream { cuda, stream })
+    }
+
+    /// Wait until all work queued on the stream is done.
+    pub fn synchronize(&self) -> Result<(), CudaError> {
+        self.cuda.check(unsafe { self.cuda.cuStreamSynchronize(self.stream) })
+    }
+}
+
+impl Drop for Stream<'_> {
+    fn drop(&mut self) {
+        let _ = unsafe { self.cuda.cuStreamDestroy_v2(self.stream) };
+    }
+}
+
+/// Page-locked host memory for `len` values of `T`, required for asynchronous copies.
+/// Empty buffers don't allocate.
+pub struct HostBuffer<'a, T: OnDevice> {
+    cuda: &'a Cuda,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<'a, T: OnDevice> HostBuffer<'a, T> {
+    pub fn alloc(cuda: &'a Cuda, len: usize) -> Result<Self, CudaError> {
+        let mut ptr = ptr::null_mut();
+        if len > 0 {
+            cuda.check(unsafe { cuda.cuMemHostAlloc(&mut ptr, len * T::size_of(), 0) })?;
+        }
+        Ok(HostBuffer { cuda, ptr: ptr.cast(), len })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<T: OnDevice> Drop for HostBuffer<'_, T> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            let _ = unsafe { self.cuda.cuMemFreeHost(self.ptr.cast()) };
+        }
+    }
+}
+
+/// Device memory for `len` values of `T`, freed on drop. Empty buffers don't allocate.
+pub struct DeviceBuffer<'a, T: OnDevice> {
+    cuda: &'a Cuda,
+    ptr: CUdeviceptr,
+    len: usize,
+    _type: PhantomData<T>,
+}
+
+impl<'a, T: OnDevice> DeviceBuffer<'a, T> {
+    pub fn alloc(cuda: &'a Cuda, len: usize) -> Result<Self, CudaError> {
+        let mut ptr = 0;
+        if len > 0 {
+            cuda.check(unsafe { cuda.cuMemAlloc_v2(&mut ptr, len * T::size_of()) })?;
+        }
+        Ok(DeviceBuffer { cuda, ptr, len, _type: PhantomData })
+    }
+
+    /// Device address, to be passed as a kernel parameter.
+    pub fn ptr(&self) -> CUdeviceptr {
+        self.ptr
+    }
+
+    /// Queue copying the first `len` values of `src` to the device.
+    ///
+    /// # Safety
+    /// `src` must stay alive and unmodified until `stream` is synchronized.
+    pub unsafe fn copy_from_host_async(&mut self, src: &HostBuffer<T>, len: usize, stream: &Stream) -> Result<(), CudaError> {
+        assert!(len <= self.len && len <= src.len);
+        self.cuda.check(self.cuda.cuMemcpyHtoDAsync_v2(self.ptr, src.ptr.cast(), len * T::size_of(), stream.stream))
+    }
+
+    /// Queue copying the first `len` values back to `dst`.
+    ///
+    /// # Safety
+    /// `dst` must stay alive and must not be accessed until `stream` is synchronized.
+    pub unsafe fn copy_to_host_async(&self, dst: &mut HostBuffer<T>, len: usize, stream: &Stream) -> Result<(), CudaError> {
+        assert!(len <= self.len && len <= dst.len);
+        self.cuda.check(self.cuda.cuMemcpyDtoHAsync_v2(dst.ptr.cast(), self.ptr, len * T::size_of(), stream.stream))
+    }
+}
+
+impl<T: OnDevice> Drop for DeviceBuffer<'_, T> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            let _ = unsafe { self.cuda.cuMemFree_v2(self.ptr) };
+        }
     }
 }
@@ -0,0 +1,178 @@
+//! Golden files with raw test outputs recorded from a CUDA library.
+//!
+//! A golden file holds everything needed to verify a test again without a GPU: whether the test
+//! compiled, the seed used to generate its inputs and its output buffers in the original batch
+//! layout. The whole file is compressed with DEFLATE (gzip container), all integers are stored
+//! little endian:
+//!
+//! ```text
+//! magic       b"PTXGOLD\0"
+//! version     u32
+//! status      u8, STATUS_COMPILED or STATUS_COMPILATION_FAIL
+//! compiled:   seed u64, output component count u32 and size u32 of every component,
+//!             then batches until the end of file, each batch is an element count u64
+//!             followed by the raw output buffer of every component
+//! failed:     message length u32 followed by the UTF-8 message
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+const MAGIC: &[u8; 8] = b"PTXGOLD\0";
+const VERSION: u32 = 2;
+const STATUS_COMPILED: u8 = 0;
+const STATUS_COMPILATION_FAIL: u8 = 1;
+
+/// What to do with golden files while running tests.
+#[derive(Clone, Copy)]
+pub enum Golden<'a> {
+    /// Record outputs of the tested CUDA library into this directory
+    Record(&'a Path),
+    /// Verify outputs recorded in this directory instead of running tests on a CUDA library
+    Replay(&'a Path),
+}
+
+fn golden_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.golden.gz"))
+}
+
+/// Writes outputs of a test that compiled successfully into its golden file.
+pub struct Recorder {
+    encoder: GzEncoder<BufWriter<File>>,
+    path: PathBuf,
+}
+
+impl Recorder {
+    pub fn create(dir: &Path, name: &str, seed: u64, output_sizes: &[usize]) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = golden_path(dir, name);
+        let file = BufWriter::new(File::create(&path)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(MAGIC)?;
+        encoder.write_all(&VERSION.to_le_bytes())?;
+        encoder.write_all(&[STATUS_COMPILED])?;
+        encoder.write_all(&seed.to_le_bytes())?;
+        encoder.write_all(&(output_sizes.len() as u32).to_le_bytes())?;
+        for size in output_sizes {
+            encoder.write_all(&(*size as u32).to_le_bytes())?;
+        }
+        Ok(Recorder { encoder, path })
+    }
+
+    /// Write a batch of `count` elements, `outputs` holds one buffer per output component.
+    pub fn batch(&mut self, count: usize, outputs: &[Vec<u8>]) -> io::Result<()> {
+        self.encoder.write_all(&(count as u64).to_le_bytes())?;
+        for output in outputs {
+            self.encoder.write_all(output)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.finish()?.flush()
+    }
+
+    /// Remove the golden file, e.g. when the test didn't get to all of its inputs.
+    pub fn discard(self) -> io::Result<()> {
+        drop(self.encoder);
+        fs::remove_file(self.path)
+    }
+}
+
+/// Write golden file of a test that failed compilation.
+pub fn record_compilation_fail(dir: &Path, name: &str, message: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let file = BufWriter::new(File::create(golden_path(dir, name))?);
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(MAGIC)?;
+    encoder.write_all(&VERSION.to_le_bytes())?;
+    encoder.write_all(&[STATUS_COMPILATION_FAIL])?;
+    encoder.write_all(&(message.len() as u32).to_le_bytes())?;
+    encoder.write_all(message.as_bytes())?;
+    encoder.finish()?.flush()
+}
+
+/// Contents of a golden file, as read back by `Replay::open`.
+pub enum Recorded {
+    CompilationFail { message: String },
+    Outputs(Box<Replay>),
+}
+
+/// Reads recorded output batches of a test back from its golden file.
+pub struct Replay {
+    decoder: GzDecoder<BufReader<File>>,
+    seed: u64,
+    output_sizes: Vec<usize>,
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+impl Replay {
+    pub fn open(dir: &Path, name: &str) -> io::Result<Recorded> {
+        let path = golden_path(dir, name);
+        let mut decoder = GzDecoder::new(BufReader::new(File::open(&path)?));
+        if &read_array::<8>(&mut decoder)? != MAGIC {
+            return Err(invalid_data(format!("{} is not a golden file", path.display())));
+        }
+        let version = u32::from_le_bytes(read_array(&mut decoder)?);
+        if version != VERSION {
+            return Err(invalid_data(format!("{} has unsupported version {version}", path.display())));
+        }
+        match read_array::<1>(&mut decoder)?[0] {
+            STATUS_COMPILED => {
+                let seed = u64::from_le_bytes(read_array(&mut decoder)?);
+                let components = u32::from_le_bytes(read_array(&mut decoder)?);
+                let output_sizes = (0..components)
+                    .map(|_| Ok(u32::from_le_bytes(read_array(&mut decoder)?) as usize))
+                    .collect::<io::Result<_>>()?;
+                Ok(Recorded::Outputs(Box::new(Replay { decoder, seed, output_sizes })))
+            }
+            STATUS_COMPILATION_FAIL => {
+                let len = u32::from_le_bytes(read_array(&mut decoder)?) as usize;
+                let mut message = vec![0; len];
+                decoder.read_exact(&mut message)?;
+                let message = String::from_utf8_lossy(&message).into_owned();
+                Ok(Recorded::CompilationFail { message })
+            }
+            status => Err(invalid_data(format!("{} has unknown status {status}", path.display()))),
+        }
+    }
+
+    /// Seed that was used to generate inputs of the recorded test.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Size of every output component, in bytes.
+    pub fn output_sizes(&self) -> &[usize] {
+        &self.output_sizes
+    }
+
+    /// Replace `outputs` with the next recorded batch, one buffer per output component.
+    /// Returns the number of elements in the batch or `None` when all batches were read.
+    pub fn next_batch(&mut self, outputs: &mut [Vec<u8>]) -> io::Result<Option<usize>> {
+        let count = match read_array::<8>(&mut self.decoder) {
+            Ok(count) => u64::from_le_bytes(count) as usize,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        for (output, size) in outputs.iter_mut().zip(&self.output_sizes) {
+            output.resize(count * size, 0);
+            self.decoder.read_exact(output)?;
+        }
+        Ok(Some(count))
+    }
+}
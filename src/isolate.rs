@@ -0,0 +1,234 @@
+//! Running every test in a separate process, so that a CUDA library crashing or hanging inside a
+//! test only fails that test instead of taking down the whole run.
+//!
+//! The parent process starts itself again once per test, with `--filter` matching just that test
+//! and `--isolated-result <path>`. The child runs the test as usual and writes its result to that
+//! file as text, one record per line with tab-separated fields:
+//!
+//! ```text
+//! Ok
+//! CompilationFail     message
+//! CompilationSuccess  name
+//! DriverError         code text
+//! ResultMismatch      total reference, followed by zero or more of
+//!   class             class count
+//!   example           input output expected
+//!   reproducer        path
+//! Crash               status stderr
+//! Timeout             seconds, followed by verified and total inputs if known
+//! ```
+//!
+//! Tabs, newlines and backslashes inside fields are escaped with a backslash. A child that exits
+//! without writing its result, or with a result that can't be read back, is reported as
+//! `TestError::Crash`. Children stop on their own when they run out of time, a child that is still
+//! running `KILL_GRACE` after its deadline is killed and reported as `TestError::Timeout`. Without
+//! a deadline children are waited for as long as they run, like tests run in-process.
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, Read},
+    num::NonZeroU32,
+    path::Path,
+    process::{self, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cuda::CudaError,
+    test::{Coverage, Mismatch, MismatchSummary, TestError, TimeLimit},
+};
+
+/// Time a child gets past its deadline to report partial coverage before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(30);
+
+/// How often a running child is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Number of trailing lines of the standard error of a crashed child kept in its `TestError`.
+const CRASH_STDERR_LINES: usize = 20;
+
+/// Run the test `name` in a child process started with `args`, limited by `time_limit`.
+pub fn run(name: &str, args: &[OsString], time_limit: TimeLimit) -> Result<(), TestError> {
+    let start = Instant::now();
+    // The rest of the budget is passed on as the budget of the child
+    let budget = time_limit.end.map(|end| end.saturating_duration_since(start).as_secs().to_string());
+    let path = env::temp_dir().join(format!("ptx_tests-{}-{name}.result", process::id()));
+    let exe = env::current_exe().unwrap_or_else(|e| panic!("Failed to locate the test executable: {e}"));
+    let mut child = Command::new(exe)
+        .args(args)
+        .args(budget.iter().flat_map(|budget| ["--budget", budget]))
+        .arg("--filter")
+        .arg(format!("^{}$", regex::escape(name)))
+        .arg("--isolated-result")
+        .arg(&path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Failed to start a process for {name}: {e}"));
+    // Read on a separate thread, a child blocked on a full pipe would look like a hang
+    let mut stderr = child.stderr.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stderr.read_to_end(&mut output);
+        String::from_utf8_lossy(&output).into_owned()
+    });
+    let deadline = time_limit.deadline(start).map(|deadline| deadline + KILL_GRACE);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap_or_else(|e| panic!("Failed to wait for {name}: {e}")) {
+            break Some(status);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            // The child may have exited in the meantime, there is nothing left to kill then
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    let stderr = reader.join().unwrap_or_default();
+    let recorded = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = match status {
+        Some(status) => status,
+        None => return Err(TestError::Timeout { timeout: start.elapsed(), coverage: None }),
+    };
+    let status = match recorded.as_deref().map(decode) {
+        Ok(Some(result)) => {
+            eprint!("{stderr}");
+            return result;
+        }
+        // E.g. the child was killed from outside while writing the file
+        Ok(None) => format!("{status}, malformed result {:?}", recorded.unwrap_or_default()),
+        Err(_) => status.to_string(),
+    };
+    let lines: Vec<_> = stderr.lines().collect();
+    let stderr = lines[lines.len().saturating_sub(CRASH_STDERR_LINES)..].join("\n");
+    Err(TestError::Crash { status, stderr })
+}
+
+/// Write the result of a test run in an isolated child, to be picked up by the parent.
+pub fn write_result(path: &Path, result: &Result<(), TestError>) -> io::Result<()> {
+    fs::write(path, encode(result))
+}
+
+/// Result as written into the result file, see the format above.
+pub fn encode(result: &Result<(), TestError>) -> String {
+    let error = match result {
+        Ok(()) => return "Ok\n".to_string(),
+        Err(error) => error,
+    };
+    let mut lines = Vec::new();
+    match error {
+        TestError::CompilationFail { message } => lines.push(vec![escape(message)]),
+        TestError::CompilationSuccess { name } => lines.push(vec![escape(name)]),
+        TestError::DriverError { error } => lines.push(vec![error.code.to_string(), escape(&error.text)]),
+        TestError::ResultMismatch { summary } => {
+            lines.push(vec![summary.total.to_string(), summary.reference.to_string()]);
+            for (class, count) in &summary.classes {
+                lines.push(vec!["class".to_string(), escape(class), count.to_string()]);
+            }
+            for Mismatch { input, output, expected } in &summary.examples {
+                lines.push(vec!["example".to_string(), escape(input), escape(output), escape(expected)]);
+            }
+            if let Some(path) = &summary.reproducer {
+                lines.push(vec!["reproducer".to_string(), escape(&path.to_string_lossy())]);
+            }
+        }
+        TestError::Crash { status, stderr } => lines.push(vec![escape(status), escape(stderr)]),
+        TestError::Timeout { timeout, coverage } => {
+            let mut fields = vec![timeout.as_secs_f64().to_string()];
+            if let Some(Coverage { verified, total }) = coverage {
+                fields.extend([verified.to_string(), total.to_string()]);
+            }
+            lines.push(fields);
+        }
+    }
+    lines[0].insert(0, error.kind().to_string());
+    lines.iter().map(|fields| fields.join("\t") + "\n").collect()
+}
+
+/// Result read back from the text written by `encode`, `None` if it is malformed.
+pub fn decode(text: &str) -> Option<Result<(), TestError>> {
+    let mut lines = text.lines().map(|line| line.split('\t').collect::<Vec<_>>());
+    let first = lines.next()?;
+    let error = match first[..] {
+        ["Ok"] => return Some(Ok(())),
+        ["CompilationFail", message] => TestError::CompilationFail { message: unescape(message) },
+        ["CompilationSuccess", name] => TestError::CompilationSuccess { name: unescape(name) },
+        ["DriverError", code, text] => {
+            let code = code.parse::<NonZeroU32>().ok()?;
+            TestError::DriverError { error: CudaError { code, text: unescape(text) } }
+        }
+        ["ResultMismatch", total, reference] => {
+            let mut summary = MismatchSummary {
+                total: total.parse().ok()?,
+                reference: reference.parse().ok()?,
+                ..Default::default()
+            };
+            for fields in lines {
+                match fields[..] {
+                    ["class", class, count] => {
+                        summary.classes.insert(unescape(class), count.parse().ok()?);
+                    }
+                    ["example", input, output, expected] => summary.examples.push(Mismatch {
+                        input: unescape(input),
+                        output: unescape(output),
+                        expected: unescape(expected),
+                    }),
+                    ["reproducer", path] => summary.reproducer = Some(unescape(path).into()),
+                    _ => return None,
+                }
+            }
+            TestError::ResultMismatch { summary }
+        }
+        ["Crash", status, stderr] => TestError::Crash { status: unescape(status), stderr: unescape(stderr) },
+        ["Timeout", seconds, ref coverage @ ..] => {
+            let timeout = Duration::from_secs_f64(seconds.parse().ok()?);
+            let coverage = match coverage {
+                [] => None,
+                [verified, total] => Some(Coverage { verified: verified.parse().ok()?, total: total.parse().ok()? }),
+                _ => return None,
+            };
+            TestError::Timeout { timeout, coverage }
+        }
+        _ => return None,
+    };
+    Some(Err(error))
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
@@ -0,0 +1,72 @@
+//! Listing tests with `--list`, optionally with what every test covers.
+
+use crate::{
+    report::json_string,
+    test::{Inputs, TestCase},
+};
+
+/// One test name per line, with `verbose` followed by its properties and kernel body.
+pub fn text(tests: &[TestCase], random_count: usize, verbose: bool) -> String {
+    let mut text = String::new();
+    for test in tests {
+        text.push_str(&test.name);
+        text.push('\n');
+        if !verbose {
+            continue;
+        }
+        let inputs = match test.tags.inputs {
+            Inputs::Exhaustive => format!("{}, exhaustive", test.input_count(random_count)),
+            Inputs::Random => format!("{} edge cases and {random_count} random", test.info.fixed_inputs),
+            Inputs::InvalidCompile => "none, invalid variants are only compiled".to_string(),
+        };
+        text.push_str(&format!("    instruction: {}\n", test.tags.instruction));
+        text.push_str(&format!("    tags: {}\n", test.tags.names().join(" ")));
+        if !test.info.input.is_empty() {
+            text.push_str(&format!("    input: {}\n", test.info.input));
+            text.push_str(&format!("    output: {}\n", test.info.output));
+        }
+        text.push_str(&format!("    inputs: {inputs}\n"));
+        text.push_str("    body:\n");
+        for line in body(test).lines().map(str::trim_end) {
+            if line.is_empty() {
+                text.push('\n');
+            } else {
+                text.push_str(&format!("        {line}\n"));
+            }
+        }
+    }
+    text
+}
+
+/// Kernel body of the test, bodies of tests with variants are preceded by a comment with the
+/// name of their variant.
+fn body(test: &TestCase) -> String {
+    if test.info.variants.is_empty() {
+        return test.info.body.clone();
+    }
+    let bodies: Vec<_> = test.info.kernels(&test.name).into_iter().map(|(name, info)| format!("// {name}\n{}", info.body)).collect();
+    bodies.join("\n")
+}
+
+/// All tests with their properties and kernel bodies as a JSON document.
+pub fn json(tests: &[TestCase], random_count: usize) -> String {
+    let tests: Vec<_> = tests
+        .iter()
+        .map(|test| {
+            let tags: Vec<_> = test.tags.names().into_iter().map(json_string).collect();
+            let fields = [
+                format!("\"name\": {}", json_string(&test.name)),
+                format!("\"instruction\": {}", json_string(test.tags.instruction)),
+                format!("\"tags\": [{}]", tags.join(", ")),
+                format!("\"inputs\": \"{}\"", test.tags.inputs.as_str()),
+                format!("\"input_type\": {}", json_string(&test.info.input)),
+                format!("\"output_type\": {}", json_string(&test.info.output)),
+                format!("\"input_count\": {}", test.input_count(random_count)),
+                // Templates are checked out with either line ending
+                format!("\"body\": {}", json_string(&body(test).replace("\r\n", "\n"))),
+            ];
+            format!("    {{ {} }}", fields.join(", "))
+        })
+        .collect();
+    format!("{{\n  \"tests\": [\n{}\n  ]\n}}\n", tests.join(",\n"))
+}
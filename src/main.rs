@@ -3,22 +3,35 @@
 #![feature(f16)]
 #![feature(c_size_t)]
 
-use std::ptr;
+use std::{collections::HashSet, ffi::OsString, iter, path::PathBuf, time::{Duration, Instant}};
 
 use bpaf::Bpaf;
 use nvrtc::Nvrtc;
 use regex::{self, Regex};
 
-use cuda::Cuda;
-use test::{TestCase, TestError};
+use checkpoint::Checkpoint;
+use cuda::{Context, Cuda};
+use report::{ReportTarget, TestResult};
+use shard::Shard;
+use test::{Coverage, Seed, TestCase, TestError, TimeLimit, RANDOM_COUNT, SEED};
 use testcase::*;
 
+mod checkpoint;
 mod common;
 mod cuda;
+mod golden;
+mod isolate;
+mod list;
 mod nvrtc;
+mod report;
+mod repro;
+mod shard;
+mod show;
 mod test;
 mod testcase;
 
+// Parsed once at startup, not worth boxing
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Bpaf)]
 #[bpaf(options)]
 pub enum Arguments {
@@ -26,16 +39,124 @@ pub enum Arguments {
         /// list all available tests, execute no tests
         #[bpaf(short, long)]
         list: (),
+
+        /// also print what every test covers: its instruction, tags, types, number of inputs and kernel body
+        #[bpaf(short, long)]
+        verbose: bool,
+
+        /// print the list with the same details as --verbose as a JSON document
+        #[bpaf(long)]
+        json: bool,
+    },
+    /// print sources of the kernels of a test as they are loaded into CUDA, without running it
+    #[bpaf(command)]
+    Show {
+        /// path to NVRTC shared library, shows the CUDA source and the PTX compiled from it when provided
+        #[bpaf(long)]
+        nvrtc: Option<String>,
+
+        /// write sources into <DIR>/<kernel name>.ptx and .cu instead of printing them
+        #[bpaf(long("dump-dir"), argument("DIR"))]
+        dump_dir: Option<PathBuf>,
+
+        /// name of the test, as printed by --list
+        #[bpaf(positional("TEST"))]
+        name: String,
     },
     Run {
-        /// only tests matching this regex will be executed
+        /// only tests matching this regex will be executed, can be repeated to run tests matching any of them
         #[bpaf(short, long)]
-        filter: Option<String>,
+        filter: Vec<String>,
+
+        /// tests matching this regex will not be executed, can be repeated
+        #[bpaf(long, argument("REGEX"))]
+        exclude: Vec<String>,
+
+        /// only tests with this tag will be executed, e.g. an instruction, a type, a rounding mode, ftz, sat, exhaustive, random or invalid, can be repeated to require all of them
+        #[bpaf(long, argument("TAG"))]
+        tag: Vec<String>,
+
+        /// run only every <count>-th test starting from the <index>-th one, with <index> from 1 to <count>, or a share of equal cost with --shard-costs
+        #[bpaf(long, argument("INDEX/COUNT"))]
+        shard: Option<Shard>,
+
+        /// balance --shard by cost estimates, one '<test name> <cost>' per line, e.g. written by --report costs=<path>
+        #[bpaf(long("shard-costs"), argument("PATH"))]
+        shard_costs: Option<PathBuf>,
+
+        /// write machine-readable test results, either json=<path>, junit=<path> or test durations as costs=<path>, can be repeated
+        #[bpaf(long, argument("FORMAT=PATH"))]
+        report: Vec<ReportTarget>,
+
+        /// run tests on inputs listed in <DIR>/<test name>.txt instead of their default inputs, if such a file exists
+        #[bpaf(long, argument("DIR"))]
+        corpus: Option<PathBuf>,
+
+        /// seed of random inputs, decimal or 0x-prefixed hexadecimal, "random" picks a new seed for this run and prints it
+        #[bpaf(long, argument("SEED"), fallback(Seed::Fixed(SEED)))]
+        seed: Seed,
 
+        /// number of random inputs of every random test, edge cases are always tested in addition
+        #[bpaf(long("random-count"), argument("N"), fallback(RANDOM_COUNT))]
+        random_count: usize,
+
+        /// stop a test after this many seconds and report how many of its inputs were verified until then
+        #[bpaf(long, argument("SECS"))]
+        timeout: Option<u64>,
+
+        /// stop testing after this many seconds in total, tests that run out of time report how many of their inputs were verified
+        #[bpaf(long, argument("SECS"))]
+        budget: Option<u64>,
+
+        /// write the result of every test into this file as soon as it finishes, for continuing an interrupted run with --resume
+        #[bpaf(long, argument("PATH"))]
+        checkpoint: Option<PathBuf>,
+
+        /// skip tests that already finished according to this checkpoint and report their recorded results, results of the remaining tests are added to it unless --checkpoint is given
+        #[bpaf(long, argument("PATH"))]
+        resume: Option<PathBuf>,
+
+        #[bpaf(external(driver))]
+        driver: Driver,
+    },
+}
+
+#[derive(Debug, Clone, Bpaf)]
+pub enum Driver {
+    Replay {
+        /// verify outputs recorded with --record in this directory instead of running tests, no CUDA library is loaded
+        #[bpaf(long, argument("DIR"))]
+        replay: PathBuf,
+    },
+    Cuda {
         /// path to NVRTC shared library, switches to testing inline PTX embedded in CUDA sources when provided
         #[bpaf(long)]
         nvrtc: Option<String>,
 
+        /// path to a reference CUDA library, switches to comparing results bit for bit against it instead of the CPU
+        #[bpaf(long("reference-cuda"), argument("LIB"))]
+        reference_cuda: Option<String>,
+
+        /// record outputs computed by the tested CUDA library into golden files in this directory
+        #[bpaf(long, argument("DIR"))]
+        record: Option<PathBuf>,
+
+        /// run every test in a separate process, so that a crash or hang of the CUDA library only fails that test
+        #[bpaf(long)]
+        isolate: bool,
+
+        /// write sources of the kernels of every test into <DIR>/<kernel name>.ptx and .cu, as with the show command
+        #[bpaf(long("dump-dir"), argument("DIR"))]
+        dump_dir: Option<PathBuf>,
+
+        /// write a standalone reproducer of the first mismatching value of every failing test into <DIR>/<test name>
+        #[bpaf(long("repro-dir"), argument("DIR"))]
+        repro_dir: Option<PathBuf>,
+
+        /// file the result of a single test is written to, used by --isolate to run tests in child processes
+        #[bpaf(long("isolated-result"), argument("PATH"), hide)]
+        isolated_result: Option<PathBuf>,
+
         /// path to CUDA shared library under testing, for example C:\Windows\System32\nvcuda.dll or /usr/lib/x86_64-linux-gnu/libcuda.so
         #[bpaf(positional("cuda"))]
         cuda: String,
@@ -48,75 +169,251 @@ fn main() {
     let mut tests = tests();
 
     match args {
-        Arguments::List { .. } => {
-            for test in tests {
-                println!("{}", test.name);
+        Arguments::List { list: (), verbose, json } => {
+            if json {
+                print!("{}", list::json(&tests, RANDOM_COUNT));
+            } else {
+                print!("{}", list::text(&tests, RANDOM_COUNT, verbose));
             }
         }
-        Arguments::Run { filter, nvrtc, cuda } => {
-            if let Some(filter) = filter {
-                let re = Regex::new(&filter).unwrap();
-                tests = tests.into_iter().filter(|t| re.is_match(&t.name)).collect();
+        Arguments::Show { nvrtc, dump_dir, name } => {
+            let test = tests.iter().find(|t| t.name == name).unwrap_or_else(|| {
+                eprintln!("No test named {name}, see --list for available tests");
+                std::process::exit(1)
+            });
+            let nvrtc = nvrtc.map(Nvrtc::new);
+            if !show::show(test, nvrtc.as_ref(), dump_dir.as_deref()) {
+                std::process::exit(1);
             }
+        }
+        Arguments::Run { filter, exclude, tag, shard, shard_costs, report, corpus, seed, random_count, timeout, budget, checkpoint, resume, driver } => {
+            let time_limit = TimeLimit {
+                timeout: timeout.map(Duration::from_secs),
+                end: budget.map(|budget| Instant::now() + Duration::from_secs(budget)),
+            };
 
-            let cuda = Cuda::new(cuda);
-            let nvrtc = nvrtc.map(Nvrtc::new);
+            let filter: Vec<_> = filter.iter().map(|filter| Regex::new(filter).unwrap()).collect();
+            let exclude: Vec<_> = exclude.iter().map(|exclude| Regex::new(exclude).unwrap()).collect();
+            tests.retain(|t| {
+                let names = t.tags.names();
+                (filter.is_empty() || filter.iter().any(|re| re.is_match(&t.name)))
+                    && !exclude.iter().any(|re| re.is_match(&t.name))
+                    && tag.iter().all(|tag| names.contains(&tag.as_str()))
+            });
+            if let Some(shard) = shard {
+                let costs = shard_costs.map(|path| shard::read_costs(&path).unwrap_or_else(|e| panic!("Failed to read costs: {e}")));
+                tests = shard::select(tests, shard, costs.as_ref());
+            }
 
-            let failures = if let Some(nvrtc) = nvrtc {
-                let libs = (cuda, nvrtc);
-                run(tests, TestFixture { libs })
-            } else {
-                let libs = (cuda,);
-                run(tests, TestFixture { libs })
+            let finished = match &resume {
+                Some(path) => checkpoint::read(path).unwrap_or_else(|e| panic!("Failed to resume: {e}")),
+                None => Vec::new(),
             };
+            let checkpoint = checkpoint.or(resume).map(|path| {
+                // Results of the interrupted run are kept, so that this run can be resumed as well
+                Checkpoint::create(&path, &finished).unwrap_or_else(|e| panic!("Failed to write {}: {e}", path.display()))
+            });
+            let selected: HashSet<_> = tests.iter().map(|t| t.name.clone()).collect();
+            let finished: Vec<_> = finished.into_iter().filter(|r| selected.contains(&r.name)).collect();
+            if !finished.is_empty() {
+                println!("Resuming, {} of {} tests already finished", finished.len(), tests.len());
+                let names: HashSet<_> = finished.iter().map(|r| r.name.clone()).collect();
+                tests.retain(|t| !names.contains(&t.name));
+            }
+            let checkpoint = checkpoint.as_ref();
+
+            let results = match driver {
+                Driver::Replay { replay } => {
+                    run(tests, &ReplayFixture { dir: replay, corpus, random_count, time_limit }, checkpoint)
+                }
+                Driver::Cuda { nvrtc, reference_cuda, record, isolate, dump_dir, repro_dir, isolated_result, cuda } => {
+                    let seed = match seed {
+                        Seed::Fixed(seed) => seed,
+                        Seed::PerRun => {
+                            let seed = rand::random();
+                            println!("Random seed: {seed:#x}, rerun with --seed {seed:#x} to reproduce");
+                            seed
+                        }
+                    };
+
+                    if isolate {
+                        // Children get the resolved seed, all of them have to test the same inputs.
+                        // The budget is passed separately to every child, see isolate::run
+                        let mut args: Vec<OsString> = vec![cuda.into()];
+                        let options = [
+                            ("--nvrtc", nvrtc.map(OsString::from)),
+                            ("--reference-cuda", reference_cuda.map(OsString::from)),
+                            ("--record", record.map(OsString::from)),
+                            ("--corpus", corpus.map(OsString::from)),
+                            ("--seed", Some(format!("{seed:#x}").into())),
+                            ("--random-count", Some(random_count.to_string().into())),
+                            ("--timeout", timeout.map(|timeout| timeout.to_string().into())),
+                            ("--dump-dir", dump_dir.map(OsString::from)),
+                            ("--repro-dir", repro_dir.map(OsString::from)),
+                        ];
+                        for (option, value) in options {
+                            if let Some(value) = value {
+                                args.extend([option.into(), value]);
+                            }
+                        }
+                        run_each(tests, time_limit, random_count, checkpoint, |t| isolate::run(&t.name, &args, time_limit))
+                    } else {
+                        let cuda = Cuda::new(cuda);
+                        let nvrtc = nvrtc.map(Nvrtc::new);
+                        let reference = reference_cuda.map(Cuda::new);
+
+                        let results = if let Some(nvrtc) = nvrtc {
+                            let libs = (cuda, nvrtc);
+                            run_cuda(tests, &TestFixture { libs, reference, record, corpus, seed, random_count, time_limit, dump_dir, repro_dir }, checkpoint)
+                        } else {
+                            let libs = (cuda,);
+                            run_cuda(tests, &TestFixture { libs, reference, record, corpus, seed, random_count, time_limit, dump_dir, repro_dir }, checkpoint)
+                        };
+                        if let (Some(path), [result]) = (isolated_result, &results[..]) {
+                            isolate::write_result(&path, &result.result)
+                                .unwrap_or_else(|e| panic!("Failed to write {}: {e}", path.display()));
+                        }
+                        results
+                    }
+                }
+            };
+            let results: Vec<_> = finished.into_iter().chain(results).collect();
+
+            for target in report {
+                target.write(&results).unwrap();
+            }
 
-            std::process::exit(failures);
+            let failures = results.iter().filter(|r| r.result.is_err()).count();
+            std::process::exit(failures as i32);
         }
     }
 }
 
-fn run(tests: Vec<TestCase>, ctx: impl TestContext) -> i32 {
-    let cuda = ctx.cuda();
+/// Run tests with a context created in the tested and the reference CUDA library, if any.
+fn run_cuda(tests: Vec<TestCase>, ctx: &dyn TestContext, checkpoint: Option<&Checkpoint>) -> Vec<TestResult> {
+    let _contexts: Vec<_> = iter::once(ctx.cuda())
+        .chain(ctx.reference_cuda())
+        .map(|cuda| Context::create(cuda).unwrap_or_else(|e| panic!("Failed to create CUDA context: {e}")))
+        .collect();
+    run(tests, ctx, checkpoint)
+}
 
-    let mut failures = 0;
+fn run(tests: Vec<TestCase>, ctx: &dyn TestContext, checkpoint: Option<&Checkpoint>) -> Vec<TestResult> {
+    run_each(tests, ctx.time_limit(), ctx.random_count(), checkpoint, |t| (t.test)(ctx, &t.name))
+}
 
-    unsafe { cuda.cuInit(0) }.unwrap();
-    let mut cuda_ctx = ptr::null_mut();
-    unsafe { cuda.cuCtxCreate_v2(&mut cuda_ctx, 0, 0) }.unwrap();
+/// Execute every test with `execute`, printing and collecting its result and adding it to the
+/// checkpoint, if any. Tests left when the budget of `time_limit` runs out are not executed at all.
+/// Tests that ran out of time are left out of the checkpoint, so that `--resume` runs them again.
+fn run_each(
+    tests: Vec<TestCase>,
+    time_limit: TimeLimit,
+    random_count: usize,
+    checkpoint: Option<&Checkpoint>,
+    mut execute: impl FnMut(TestCase) -> Result<(), TestError>,
+) -> Vec<TestResult> {
+    let mut results = Vec::new();
 
     for t in tests {
-        use TestError::*;
+        let name = t.name.clone();
+        let start = Instant::now();
+        let result = if time_limit.end.is_some_and(|end| start >= end) {
+            // Loading and compiling the kernel would only be wasted on a test that can't run anyway
+            let coverage = Some(Coverage { verified: 0, total: t.input_count(random_count) });
+            Err(TestError::Timeout { timeout: Duration::ZERO, coverage })
+        } else {
+            execute(t)
+        };
+        let duration = start.elapsed();
 
-        let result = (t.test)(&ctx);
-        if result.is_err() {
-            failures += 1;
+        print!("{name}: ");
+        match &result {
+            Ok(()) => println!("OK"),
+            Err(e) => println!("FAIL - {e}"),
         }
 
-        print!("{}: ", t.name);
-        match result {
-            Ok(()) => println!("OK"),
-            Err(CompilationFail { message }) => println!("FAIL - Compilation failed:\n{message}"),
-            Err(CompilationSuccess { name }) => println!("FAIL - Compilation mismatch, didn't expect '{name}' to compile"),
-            Err(ResultMismatch { input, output, expected }) => println!(
-                "FAIL - with input {input}\n    computed on GPU: {output}\n    computed on CPU: {expected}"
-            ),
+        let result = TestResult { name, result, duration };
+        let timed_out = matches!(result.result, Err(TestError::Timeout { .. }));
+        if let (Some(checkpoint), false) = (checkpoint, timed_out) {
+            checkpoint.append(&result).unwrap_or_else(|e| panic!("Failed to write checkpoint: {e}"));
         }
+        results.push(result);
     }
 
-    failures
+    results
 }
 
+/// Define a struct wrapping a shared library, with all listed functions resolved once when the
+/// library is loaded and exposed as `unsafe` methods of the same name.
 #[macro_export]
 macro_rules! impl_library {
-    ($($abi:literal fn $fn_name:ident( $($arg_id:ident : $arg_type:ty),* $(,)* ) -> $ret_type:ty);* $(;)*) => {
-        $(
+    ($vis:vis struct $name:ident; $($abi:literal fn $fn_name:ident( $($arg_id:ident : $arg_type:ty),* $(,)* ) -> $ret_type:ty);* $(;)*) => {
+        #[allow(non_snake_case)]
+        #[allow(improper_ctypes_definitions)]
+        $vis struct $name {
+            $( $fn_name: unsafe extern $abi fn( $($arg_type),*) -> $ret_type, )*
+            // Function pointers above are only valid while the library stays loaded
+            _library: libloading::Library,
+        }
+
+        impl $name {
             #[allow(non_snake_case)]
-            #[allow(improper_ctypes)]
-            pub unsafe fn $fn_name(&self,  $( $arg_id : $arg_type),*) -> $ret_type {
-                let fn_: libloading::Symbol<unsafe extern $abi fn( $($arg_type),*) -> $ret_type> =
-                    self.library.get(concat!(stringify!($fn_name), "\0").as_bytes()).unwrap();
-                fn_( $($arg_id),*)
+            pub fn new(path: String) -> Self {
+                let library = unsafe { libloading::Library::new(&path) }.unwrap_or_else(|e| panic!("Failed to load {path}: {e}"));
+                $(
+                    let symbol = concat!(stringify!($fn_name), "\0").as_bytes();
+                    let $fn_name = *unsafe { library.get(symbol) }.unwrap_or_else(|e| panic!("Failed to load {path}: {e}"));
+                )*
+                Self { $( $fn_name, )* _library: library }
             }
-        )*
+
+            $(
+                #[allow(non_snake_case)]
+                pub unsafe fn $fn_name(&self,  $( $arg_id : $arg_type),*) -> $ret_type {
+                    (self.$fn_name)( $($arg_id),*)
+                }
+            )*
+        }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process, thread};
+    use test::{Inputs, Tags, TestInfo};
+
+    fn exhaustive_test(name: &str) -> TestCase {
+        let info = TestInfo {
+            input: "u32".to_string(),
+            output: "u32".to_string(),
+            fixed_inputs: 1 << 32,
+            args: Vec::new(),
+            body: String::new(),
+            variants: Vec::new(),
+        };
+        let tags = Tags { inputs: Inputs::Exhaustive, ..Default::default() };
+        TestCase { test: Box::new(|_, _| Ok(())), name: name.to_string(), tags, info }
+    }
+
+    #[test]
+    fn resume_runs_tests_skipped_by_budget() {
+        let path = std::env::temp_dir().join(format!("ptx_tests_checkpoint_{}", process::id()));
+        let checkpoint = Checkpoint::create(&path, &[]).unwrap();
+
+        // The budget runs out while the first test runs, the other two are skipped
+        let time_limit = TimeLimit { timeout: None, end: Some(Instant::now() + Duration::from_millis(50)) };
+        let tests = ["first", "second", "third"].map(exhaustive_test).into();
+        let results = run_each(tests, time_limit, RANDOM_COUNT, Some(&checkpoint), |_| {
+            thread::sleep(Duration::from_millis(100));
+            Ok(())
+        });
+        assert!(results[0].result.is_ok());
+        assert!(results[1..].iter().all(|r| matches!(r.result, Err(TestError::Timeout { .. }))));
+
+        let finished = checkpoint::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let finished: Vec<_> = finished.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(finished, ["first"]);
+    }
+}
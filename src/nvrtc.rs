@@ -2,8 +2,6 @@
 
 use std::num::NonZeroU32;
 
-use libloading::Library;
-
 use crate::impl_library;
 
 
@@ -19,53 +17,41 @@ pub struct nvrtcProgram_ {
 pub type nvrtcProgram = *mut nvrtcProgram_;
 
 
-pub struct Nvrtc {
-    library: Library,
-}
-
-impl Nvrtc {
-    pub fn new(path: String) -> Self {
-        let library = unsafe { Library::new(path).unwrap() };
-        Self { library }
-    }
-}
-
-impl Nvrtc {
-    impl_library! {
-        "system" fn nvrtcCreateProgram(
-            prog: *mut nvrtcProgram,
-            src: *const ::core::ffi::c_char,
-            name: *const ::core::ffi::c_char,
-            numHeaders: ::core::ffi::c_int,
-            headers: *const *const ::core::ffi::c_char,
-            includeNames: *const *const ::core::ffi::c_char,
-        ) -> nvrtcResult;
-        "system" fn nvrtcCompileProgram(
-            prog: nvrtcProgram,
-            numOptions: ::core::ffi::c_int,
-            options: *const *const ::core::ffi::c_char,
-        ) -> nvrtcResult;
-        "system" fn nvrtcDestroyProgram(
-            prog: *mut nvrtcProgram,
-        ) -> nvrtcResult;
-        "system" fn nvrtcGetPTXSize(
-            prog: nvrtcProgram,
-            ptxSizeRet: *mut ::core::ffi::c_size_t,
-        ) -> nvrtcResult;
-        "system" fn nvrtcGetPTX(
-            prog: nvrtcProgram,
-            ptx: *mut ::core::ffi::c_char,
-        ) -> nvrtcResult;
-        "system" fn nvrtcGetProgramLogSize(
-            prog: nvrtcProgram,
-            logSizeRet: *mut ::core::ffi::c_size_t,
-        ) -> nvrtcResult;
-        "system" fn nvrtcGetProgramLog(
-            prog: nvrtcProgram,
-            log: *mut ::core::ffi::c_char,
-        ) -> nvrtcResult;
-        "system" fn nvrtcGetErrorString(
-            result: nvrtcResult,
-        ) -> *const ::core::ffi::c_char;
-    }
+impl_library! {
+    pub struct Nvrtc;
+    "system" fn nvrtcCreateProgram(
+        prog: *mut nvrtcProgram,
+        src: *const ::core::ffi::c_char,
+        name: *const ::core::ffi::c_char,
+        numHeaders: ::core::ffi::c_int,
+        headers: *const *const ::core::ffi::c_char,
+        includeNames: *const *const ::core::ffi::c_char,
+    ) -> nvrtcResult;
+    "system" fn nvrtcCompileProgram(
+        prog: nvrtcProgram,
+        numOptions: ::core::ffi::c_int,
+        options: *const *const ::core::ffi::c_char,
+    ) -> nvrtcResult;
+    "system" fn nvrtcDestroyProgram(
+        prog: *mut nvrtcProgram,
+    ) -> nvrtcResult;
+    "system" fn nvrtcGetPTXSize(
+        prog: nvrtcProgram,
+        ptxSizeRet: *mut ::core::ffi::c_size_t,
+    ) -> nvrtcResult;
+    "system" fn nvrtcGetPTX(
+        prog: nvrtcProgram,
+        ptx: *mut ::core::ffi::c_char,
+    ) -> nvrtcResult;
+    "system" fn nvrtcGetProgramLogSize(
+        prog: nvrtcProgram,
+        logSizeRet: *mut ::core::ffi::c_size_t,
+    ) -> nvrtcResult;
+    "system" fn nvrtcGetProgramLog(
+        prog: nvrtcProgram,
+        log: *mut ::core::ffi::c_char,
+    ) -> nvrtcResult;
+    "system" fn nvrtcGetErrorString(
+        result: nvrtcResult,
+    ) -> *const ::core::ffi::c_char;
 }
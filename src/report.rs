@@ -0,0 +1,210 @@
+use std::{fs, io, path::PathBuf, str::FromStr, time::Duration};
+
+use crate::test::{Mismatch, TestError};
+
+/// Outcome of a single executed test.
+pub struct TestResult {
+    pub name: String,
+    pub result: Result<(), TestError>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+    /// Duration of every test, as read by `--shard-costs`
+    Costs,
+}
+
+/// Machine-readable report requested on the command line as `<format>=<path>`.
+#[derive(Debug, Clone)]
+pub struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for ReportTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <format>=<path>, got '{s}'"))?;
+        let format = match format {
+            "json" => ReportFormat::Json,
+            "junit" => ReportFormat::Junit,
+            "costs" => ReportFormat::Costs,
+            _ => return Err(format!("unknown report format '{format}', expected 'json', 'junit' or 'costs'")),
+        };
+        if path.is_empty() {
+            return Err(format!("missing path for '{s}'"));
+        }
+        Ok(ReportTarget { format, path: PathBuf::from(path) })
+    }
+}
+
+impl ReportTarget {
+    pub fn write(&self, results: &[TestResult]) -> io::Result<()> {
+        let text = match self.format {
+            ReportFormat::Json => json(results),
+            ReportFormat::Junit => junit(results),
+            ReportFormat::Costs => costs(results),
+        };
+        fs::write(&self.path, text)
+    }
+}
+
+fn json(results: &[TestResult]) -> String {
+    let tests: Vec<_> = results.iter().map(json_test).collect();
+    format!("{{\n  \"tests\": [\n{}\n  ]\n}}\n", tests.join(",\n"))
+}
+
+fn json_test(result: &TestResult) -> String {
+    let mut fields = vec![
+        format!("\"name\": {}", json_string(&result.name)),
+        format!("\"status\": \"{}\"", if result.result.is_ok() { "pass" } else { "fail" }),
+        format!("\"duration\": {:.3}", result.duration.as_secs_f64()),
+    ];
+    if let Err(error) = &result.result {
+        fields.push(format!("\"error\": {}", json_error(error)));
+    }
+    format!("    {{ {} }}", fields.join(", "))
+}
+
+fn json_error(error: &TestError) -> String {
+    let kind = format!("\"kind\": \"{}\"", error.kind());
+    match error {
+        TestError::CompilationFail { message } => {
+            format!("{{ {kind}, \"message\": {} }}", json_string(message))
+        }
+        TestError::CompilationSuccess { name } => {
+            format!("{{ {kind}, \"name\": {} }}", json_string(name))
+        }
+        TestError::DriverError { error } => {
+            format!("{{ {kind}, \"code\": {}, \"message\": {} }}", error.code, json_string(&error.text))
+        }
+        TestError::Crash { status, stderr } => {
+            format!("{{ {kind}, \"status\": {}, \"stderr\": {} }}", json_string(status), json_string(stderr))
+        }
+        TestError::Timeout { timeout, coverage } => {
+            let coverage = match coverage {
+                Some(coverage) => format!(", \"verified\": {}, \"total\": {}", coverage.verified, coverage.total),
+                None => String::new(),
+            };
+            format!("{{ {kind}, \"timeout\": {:.3}{coverage} }}", timeout.as_secs_f64())
+        }
+        TestError::ResultMismatch { summary } => {
+            let examples: Vec<_> = summary
+                .examples
+                .iter()
+                .map(|Mismatch { input, output, expected }| {
+                    format!(
+                        "{{ \"input\": {}, \"output\": {}, \"expected\": {} }}",
+                        json_string(input),
+                        json_string(output),
+                        json_string(expected),
+                    )
+                })
+                .collect();
+            let classes: Vec<_> = summary
+                .classes
+                .iter()
+                .map(|(class, count)| format!("{}: {count}", json_string(class)))
+                .collect();
+            let reproducer = match &summary.reproducer {
+                Some(path) => format!(", \"reproducer\": {}", json_string(&path.to_string_lossy())),
+                None => String::new(),
+            };
+            format!(
+                "{{ {kind}, \"total\": {}, \"reference\": {}, \"examples\": [{}], \"classes\": {{ {} }}{reproducer} }}",
+                summary.total,
+                summary.reference,
+                examples.join(", "),
+                classes.join(", "),
+            )
+        }
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn junit(results: &[TestResult]) -> String {
+    let failures = results.iter().filter(|r| r.result.is_err()).count();
+    let time: f64 = results.iter().map(|r| r.duration.as_secs_f64()).sum();
+    let mut text = String::new();
+    text.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    text.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{failures}\" time=\"{time:.3}\">\n",
+        results.len(),
+    ));
+    text.push_str(&format!(
+        "  <testsuite name=\"ptx_tests\" tests=\"{}\" failures=\"{failures}\" time=\"{time:.3}\">\n",
+        results.len(),
+    ));
+    for result in results {
+        let attributes = format!(
+            "name=\"{}\" classname=\"ptx_tests\" time=\"{:.3}\"",
+            xml_escape(&result.name),
+            result.duration.as_secs_f64(),
+        );
+        match &result.result {
+            Ok(()) => text.push_str(&format!("    <testcase {attributes}/>\n")),
+            Err(error) => {
+                let details = error.to_string();
+                let message = details.lines().next().unwrap_or_default();
+                text.push_str(&format!("    <testcase {attributes}>\n"));
+                text.push_str(&format!(
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                    error.kind(),
+                    xml_escape(message),
+                    xml_escape(&details),
+                ));
+                text.push_str("    </testcase>\n");
+            }
+        }
+    }
+    text.push_str("  </testsuite>\n");
+    text.push_str("</testsuites>\n");
+    text
+}
+
+fn costs(results: &[TestResult]) -> String {
+    results
+        .iter()
+        .map(|result| format!("{} {:.3}\n", result.name, result.duration.as_secs_f64()))
+        .collect()
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => escaped.push('?'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,112 @@
+// Reproducer of a mismatching result of the ptx_tests test <TEST>.
+//
+// Loads kernel.ptx, runs its kernel on the single failing input and compares the result bit for
+// bit with the one computed <ORACLE>. Only the CUDA driver library is needed, build and run with:
+//
+//     cc main.c -o repro -lcuda && ./repro [path/to/kernel.ptx]
+//
+// Exits with 0 when the result is as expected, 1 when it differs and 2 when a CUDA call fails.
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+// The few CUDA driver API functions used below, declared here so that no CUDA headers are needed
+#ifdef _WIN32
+#define CUDAAPI __stdcall
+#else
+#define CUDAAPI
+#endif
+typedef int CUresult;
+typedef void *CUcontext;
+typedef void *CUmodule;
+typedef void *CUfunction;
+typedef void *CUstream;
+typedef unsigned long long CUdeviceptr;
+CUresult CUDAAPI cuInit(unsigned int flags);
+CUresult CUDAAPI cuCtxCreate_v2(CUcontext *context, unsigned int flags, int device);
+CUresult CUDAAPI cuGetErrorString(CUresult error, const char **message);
+CUresult CUDAAPI cuModuleLoadDataEx(CUmodule *module, const void *image, unsigned int count, int *options,
+                                    void **values);
+CUresult CUDAAPI cuModuleGetFunction(CUfunction *function, CUmodule module, const char *name);
+CUresult CUDAAPI cuMemAlloc_v2(CUdeviceptr *pointer, size_t size);
+CUresult CUDAAPI cuMemcpyHtoDAsync_v2(CUdeviceptr destination, const void *source, size_t size, CUstream stream);
+CUresult CUDAAPI cuMemcpyDtoHAsync_v2(void *destination, CUdeviceptr source, size_t size, CUstream stream);
+CUresult CUDAAPI cuStreamCreate(CUstream *stream, unsigned int flags);
+CUresult CUDAAPI cuStreamSynchronize(CUstream stream);
+CUresult CUDAAPI cuLaunchKernel(CUfunction function, unsigned int grid_x, unsigned int grid_y, unsigned int grid_z,
+                                unsigned int block_x, unsigned int block_y, unsigned int block_z,
+                                unsigned int shared_memory, CUstream stream, void **params, void **extra);
+
+// Same JIT options and stream flags as the test harness
+#define CU_JIT_ERROR_LOG_BUFFER 5
+#define CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES 6
+#define CU_STREAM_NON_BLOCKING 1
+
+static void check(CUresult result, const char *call) {
+    if (result != 0) {
+        const char *message = NULL;
+        cuGetErrorString(result, &message);
+        fprintf(stderr, "%s failed with %d: %s\n", call, result, message ? message : "unknown error");
+        exit(2);
+    }
+}
+
+#define CHECK(call) check(call, #call)
+
+static char *read_file(const char *path) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        perror(path);
+        exit(2);
+    }
+    fseek(file, 0, SEEK_END);
+    long size = ftell(file);
+    fseek(file, 0, SEEK_SET);
+    char *text = malloc(size + 1);
+    if (!text || fread(text, 1, size, file) != (size_t)size) {
+        perror(path);
+        exit(2);
+    }
+    text[size] = 0;
+    fclose(file);
+    return text;
+}
+
+int main(int argc, char **argv) {
+    char *ptx = read_file(argc > 1 ? argv[1] : "kernel.ptx");
+    CUcontext context;
+    CUmodule module;
+    CUfunction function;
+    CUstream stream;
+    CHECK(cuInit(0));
+    CHECK(cuCtxCreate_v2(&context, 0, 0));
+    static char error_log[16384];
+    int options[] = {CU_JIT_ERROR_LOG_BUFFER, CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES};
+    void *values[] = {error_log, (void *)(size_t)sizeof(error_log)};
+    CUresult load = cuModuleLoadDataEx(&module, ptx, 2, options, values);
+    if (load != 0 && error_log[0]) {
+        fprintf(stderr, "%s\n", error_log);
+    }
+    CHECK(load);
+    CHECK(cuModuleGetFunction(&function, module, "run"));
+    CHECK(cuStreamCreate(&stream, CU_STREAM_NON_BLOCKING));
+
+    // Every component of inputs and outputs has a buffer of its own, kernel parameters are
+    // pointers to all input buffers followed by all output buffers
+<DECLARATIONS>
+    CUdeviceptr buffers[<BUFFERS>];
+    void *params[<BUFFERS>];
+    for (int i = 0; i < <BUFFERS>; i++) {
+        CHECK(cuMemAlloc_v2(&buffers[i], sizes[i]));
+        params[i] = &buffers[i];
+    }
+<UPLOAD>
+    // A single thread, which processes the value at index 0 of every buffer
+    CHECK(cuLaunchKernel(function, 1, 1, 1, 1, 1, 1, 0, stream, params, NULL));
+<DOWNLOAD>
+    CHECK(cuStreamSynchronize(stream));
+    int mismatch = 0;
+<COMPARE>
+    puts(mismatch ? "Result differs from expected" : "Result is as expected");
+    return mismatch;
+}
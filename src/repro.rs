@@ -0,0 +1,114 @@
+//! Standalone reproducers of mismatching results, to hand over to developers of the tested CUDA
+//! library.
+//!
+//! A reproducer of the test `<name>` is a directory `<name>` with the PTX module loaded by the test
+//! in `kernel.ptx`, a C program in `main.c` running it on the first mismatching input and checking
+//! the result bit for bit, and a description of the mismatch in `mismatch.txt`. The program only
+//! needs the CUDA driver library, see `repro.c` for how to build it.
+
+use std::{
+    ffi::CStr,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::test::{MismatchBits, MismatchSummary};
+
+static MAIN: &str = include_str!("repro.c");
+
+/// Write the reproducer of the first mismatch of the test `name`, which loaded the module `ptx`.
+/// Returns the directory of the reproducer.
+pub fn write(dir: &Path, name: &str, ptx: &CStr, summary: &MismatchSummary, bits: &MismatchBits) -> io::Result<PathBuf> {
+    let dir = dir.join(name);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("kernel.ptx"), ptx.to_bytes())?;
+    fs::write(dir.join("main.c"), main(name, summary.reference, bits))?;
+    fs::write(dir.join("mismatch.txt"), description(name, summary, bits))?;
+    Ok(dir)
+}
+
+fn oracle(reference: bool) -> &'static str {
+    if reference {
+        "by the reference CUDA library"
+    } else {
+        "on CPU"
+    }
+}
+
+/// Components of a value as hexadecimal bit patterns, e.g. `0x3f800000 0x1f`.
+fn hex(components: &[Vec<u8>]) -> Vec<String> {
+    components
+        .iter()
+        .map(|bytes| {
+            let mut value = [0; 8];
+            value[..bytes.len()].copy_from_slice(bytes);
+            format!("{:#0width$x}", u64::from_le_bytes(value), width = 2 + 2 * bytes.len())
+        })
+        .collect()
+}
+
+fn c_type(size: usize) -> &'static str {
+    match size {
+        1 => "uint8_t",
+        2 => "uint16_t",
+        4 => "uint32_t",
+        8 => "uint64_t",
+        _ => panic!("Unsupported component size {size}"),
+    }
+}
+
+fn main(name: &str, reference: bool, bits: &MismatchBits) -> String {
+    let inputs = (0..bits.input.len()).map(|i| format!("input{i}"));
+    let outputs = (0..bits.output.len()).map(|i| format!("output{i}"));
+    let buffers: Vec<_> = inputs.chain(outputs).collect();
+    let mut declarations = String::new();
+    for (i, value) in hex(&bits.input).into_iter().enumerate() {
+        declarations.push_str(&format!("    {} input{i} = {value};\n", c_type(bits.input[i].len())));
+    }
+    for (i, value) in hex(&bits.expected).into_iter().enumerate() {
+        let c_type = c_type(bits.expected[i].len());
+        declarations.push_str(&format!("    {c_type} output{i} = 0;\n    const {c_type} expected{i} = {value};\n"));
+    }
+    let sizes: Vec<_> = buffers.iter().map(|variable| format!("sizeof({variable})")).collect();
+    declarations.push_str(&format!("    const size_t sizes[] = {{{}}};", sizes.join(", ")));
+    let mut upload = Vec::new();
+    let mut download = Vec::new();
+    for (buffer, variable) in buffers.iter().enumerate() {
+        if variable.starts_with("input") {
+            upload.push(format!("    CHECK(cuMemcpyHtoDAsync_v2(buffers[{buffer}], &{variable}, sizeof({variable}), stream));"));
+        } else {
+            download.push(format!("    CHECK(cuMemcpyDtoHAsync_v2(&{variable}, buffers[{buffer}], sizeof({variable}), stream));"));
+        }
+    }
+    let compare: Vec<_> = bits
+        .output
+        .iter()
+        .enumerate()
+        .map(|(i, bytes)| {
+            let width = 2 * bytes.len();
+            format!(
+                "    printf(\"output{i}: 0x%0{width}llx, expected 0x%0{width}llx\\n\", (unsigned long long)output{i}, (unsigned long long)expected{i});\n    mismatch |= output{i} != expected{i};"
+            )
+        })
+        .collect();
+    MAIN.replace("<TEST>", name)
+        .replace("<ORACLE>", oracle(reference))
+        .replace("<DECLARATIONS>", &declarations)
+        .replace("<BUFFERS>", &buffers.len().to_string())
+        .replace("<UPLOAD>", &upload.join("\n"))
+        .replace("<DOWNLOAD>", &download.join("\n"))
+        .replace("<COMPARE>", &compare.join("\n"))
+}
+
+fn description(name: &str, summary: &MismatchSummary, bits: &MismatchBits) -> String {
+    let example = &summary.examples[0];
+    let mut text = format!("{name}: {} mismatching values, the first one is reproduced by main.c\n\n", summary.total);
+    text.push_str(&format!("input: {}\n", example.input));
+    text.push_str(&format!("computed on GPU: {}\n", example.output));
+    text.push_str(&format!("computed {}: {}\n\n", oracle(summary.reference), example.expected));
+    text.push_str(&format!("input bits: {}\n", hex(&bits.input).join(" ")));
+    text.push_str(&format!("output bits: {}\n", hex(&bits.output).join(" ")));
+    text.push_str(&format!("expected bits: {}\n", hex(&bits.expected).join(" ")));
+    text
+}
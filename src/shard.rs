@@ -0,0 +1,85 @@
+//! Splitting tests between several machines running the suite in parallel.
+//!
+//! Every worker is given the same test list and its own `--shard <index>/<count>`, shards are
+//! disjoint and together cover the whole list. Without cost estimates tests are dealt out in turn,
+//! so families of similar tests next to each other in the sorted list end up spread over all
+//! shards. With cost estimates, e.g. durations recorded by `--report costs=<path>` in an earlier
+//! run, the most expensive tests are assigned first, each one to the shard with the lowest total
+//! cost so far.
+
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+use crate::test::TestCase;
+
+/// Shard requested on the command line as `<index>/<count>`, indices start at 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected <index>/<count>, got '{s}'"))?;
+        let index = index.parse().map_err(|e| format!("invalid shard index '{index}': {e}"))?;
+        let count = count.parse().map_err(|e| format!("invalid shard count '{count}': {e}"))?;
+        if index == 0 || index > count {
+            return Err(format!("shard index must be between 1 and {count}, got {index}"));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+/// Read cost estimates, one test per line as `<name> <cost>`, e.g. a duration in seconds.
+pub fn read_costs(path: &Path) -> Result<HashMap<String, f64>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut costs = HashMap::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("{}:{}: {message}", path.display(), line_index + 1);
+        let (name, cost) = line.split_once(char::is_whitespace).ok_or_else(|| error("expected <name> <cost>"))?;
+        let cost = cost.trim().parse::<f64>().map_err(|e| error(&e.to_string()))?;
+        if !cost.is_finite() || cost < 0.0 {
+            return Err(error("cost must be a non-negative number"));
+        }
+        costs.insert(name.to_string(), cost);
+    }
+    Ok(costs)
+}
+
+/// Keep only the tests of `shard`, in their original order. Tests without an estimate in `costs`
+/// are assumed to cost as much as an average test that has one.
+pub fn select(tests: Vec<TestCase>, shard: Shard, costs: Option<&HashMap<String, f64>>) -> Vec<TestCase> {
+    let assignment: Vec<usize> = match costs {
+        None => (0..tests.len()).map(|i| i % shard.count).collect(),
+        Some(costs) => {
+            let known: Vec<f64> = tests.iter().filter_map(|t| costs.get(&t.name).copied()).collect();
+            let default = if known.is_empty() { 1.0 } else { known.iter().sum::<f64>() / known.len() as f64 };
+            let cost = |t: &TestCase| costs.get(&t.name).copied().unwrap_or(default);
+            // Ties are broken by position in the list, so that every worker computes the same assignment
+            let mut order: Vec<usize> = (0..tests.len()).collect();
+            order.sort_by(|&a, &b| cost(&tests[b]).total_cmp(&cost(&tests[a])).then(a.cmp(&b)));
+            let mut totals = vec![0.0f64; shard.count];
+            let mut assignment = vec![0; tests.len()];
+            for i in order {
+                let lightest = (0..shard.count).min_by(|&a, &b| totals[a].total_cmp(&totals[b])).unwrap();
+                totals[lightest] += cost(&tests[i]);
+                assignment[i] = lightest;
+            }
+            assignment
+        }
+    };
+    tests
+        .into_iter()
+        .zip(assignment)
+        .filter(|(_, assigned)| *assigned == shard.index - 1)
+        .map(|(t, _)| t)
+        .collect()
+}
@@ -0,0 +1,51 @@
+//! Sources of test kernels exactly as they are handed to the tested CUDA library, for debugging a
+//! test without re-deriving its kernel from the template.
+//!
+//! Every kernel has a `<name>.ptx` file with the PTX module loaded into CUDA and, when testing
+//! through NVRTC, a `<name>.cu` file with the CUDA source the PTX was compiled from. Tests of
+//! invalid instruction variants have one kernel per variant, named after the variant.
+
+use std::{fs, path::Path};
+
+use crate::{nvrtc::Nvrtc, test::TestCase, testcase};
+
+/// Write `text` into `<dir>/<name>.<extension>`, if there is a directory to dump sources into.
+pub fn dump(dir: Option<&Path>, name: &str, extension: &str, text: &str) {
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir)
+            .and_then(|()| fs::write(dir.join(format!("{name}.{extension}")), text))
+            .unwrap_or_else(|e| panic!("Failed to dump {name}.{extension}: {e}"));
+    }
+}
+
+/// Generate sources of all kernels of `test` without running it, into `dump_dir` or to stdout.
+/// Returns `false` if any kernel failed NVRTC compilation, which is expected of invalid variants.
+pub fn show(test: &TestCase, nvrtc: Option<&Nvrtc>, dump_dir: Option<&Path>) -> bool {
+    let mut compiled = true;
+    for (name, info) in test.info.kernels(&test.name) {
+        let args: Vec<_> = info.args.iter().map(String::as_str).collect();
+        let mut files = Vec::new();
+        match nvrtc {
+            None => files.push(("ptx", testcase::ptx_source(&args, &info.body))),
+            Some(nvrtc) => {
+                let source_cuda = testcase::cuda_source(&args, &info.body);
+                let source_ptx = testcase::compile_cuda(nvrtc, &source_cuda);
+                files.push(("cu", source_cuda));
+                match source_ptx {
+                    Ok(source_ptx) => files.push(("ptx", source_ptx.to_string_lossy().into_owned())),
+                    Err(message) => {
+                        eprintln!("{name}: {message}");
+                        compiled = false;
+                    }
+                }
+            }
+        }
+        for (extension, text) in files {
+            match dump_dir {
+                Some(_) => dump(dump_dir, name, extension, &text),
+                None => println!("// {name}.{extension}\n{text}\n"),
+            }
+        }
+    }
+    compiled
+}
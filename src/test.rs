@@ -1,18 +1,19 @@
-use half::f16;
-use num::{Bounded, Num, PrimInt, Zero};
+use half::{bf16, f16};
+use num::{Bounded, Float, Num, Zero};
 use rand::{Rng, SeedableRng};
+use regex::Regex;
 use rand_xorshift::XorShiftRng;
-use std::{any::Any, fmt::Debug, mem, ptr, u32};
+use std::{any::{self, Any}, collections::BTreeMap, ffi::{CStr, CString}, fmt::{self, Debug}, fs, mem, num::{FpCategory, NonZeroUsize}, panic, path::{Path, PathBuf}, str::FromStr, sync::LazyLock, thread, time::{Duration, Instant}, u32};
 
-use crate::{cuda::CUmodule, TestContext};
+use crate::{common, cuda::{Cuda, CudaError, DeviceBuffer, HostBuffer, LoadError, Module, Stream}, golden::{self, Golden, Recorded, Recorder, Replay}, repro, TestContext};
 
 pub trait TestPtx {
     fn args(&self) -> &[&str];
     fn body(&self) -> String;
 }
 
-pub trait TestCommon: TestPtx {
-    type Input: OnDevice + DebugRich;
+pub trait TestCommon: TestPtx + Sync {
+    type Input: OnDevice + DebugRich + InputClass;
     type Output: OnDevice + DebugRich;
 
     fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output>;
@@ -23,14 +24,15 @@ pub trait RangeTest: TestCommon {
     fn generate(&self, input: u32) -> Self::Input;
 }
 
-pub trait RandomTest: TestCommon + Default {
+pub trait RandomTest: TestCommon {
     fn generate<R: Rng>(rng: &mut R) -> Self::Input;
 }
 
-pub trait OnDevice: Copy + Debug {
+pub trait OnDevice: Copy + Debug + Sync {
     const COMPONENTS: usize;
     fn write(self, buffers: &mut [Vec<u8>]);
     fn read(buffers: &[Vec<u8>], index: usize) -> Self;
+    fn same_bits(self, other: Self) -> bool;
     fn size_of() -> usize {
         mem::size_of::<Self>()
     }
@@ -55,6 +57,10 @@ impl OnDevice for u8 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for u16 {
     const COMPONENTS: usize = 1;
@@ -72,6 +78,10 @@ impl OnDevice for u16 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for i16 {
     const COMPONENTS: usize = 1;
@@ -89,6 +99,10 @@ impl OnDevice for i16 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 
 impl OnDevice for u32 {
@@ -107,6 +121,10 @@ impl OnDevice for u32 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for i32 {
     const COMPONENTS: usize = 1;
@@ -124,6 +142,10 @@ impl OnDevice for i32 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for u64 {
     const COMPONENTS: usize = 1;
@@ -141,6 +163,10 @@ impl OnDevice for u64 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for i64 {
     const COMPONENTS: usize = 1;
@@ -158,6 +184,10 @@ impl OnDevice for i64 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for f16 {
     const COMPONENTS: usize = 1;
@@ -175,6 +205,31 @@ impl OnDevice for f16 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
+}
+impl OnDevice for bf16 {
+    const COMPONENTS: usize = 1;
+
+    fn write(self, buffers: &mut [Vec<u8>]) {
+        buffers[0].extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read(buffers: &[Vec<u8>], index: usize) -> Self {
+        unsafe {
+            buffers[0]
+                .as_ptr()
+                .cast::<Self>()
+                .add(index)
+                .read_unaligned()
+        }
+    }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for f32 {
     const COMPONENTS: usize = 1;
@@ -192,6 +247,10 @@ impl OnDevice for f32 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl OnDevice for f64 {
     const COMPONENTS: usize = 1;
@@ -209,6 +268,10 @@ impl OnDevice for f64 {
                 .read_unaligned()
         }
     }
+
+    fn same_bits(self, other: Self) -> bool {
+        self.to_le_bytes() == other.to_le_bytes()
+    }
 }
 impl<X: OnDevice, Y: OnDevice> OnDevice for (X, Y) {
     const COMPONENTS: usize = 2;
@@ -222,6 +285,10 @@ impl<X: OnDevice, Y: OnDevice> OnDevice for (X, Y) {
         (X::read(&buffers[0..], index), Y::read(&buffers[1..], index))
     }
 
+    fn same_bits(self, other: Self) -> bool {
+        self.0.same_bits(other.0) && self.1.same_bits(other.1)
+    }
+
     fn size_of() -> usize {
         X::size_of() + Y::size_of()
     }
@@ -248,6 +315,10 @@ impl<X: OnDevice, Y: OnDevice, Z: OnDevice> OnDevice for (X, Y, Z) {
         )
     }
 
+    fn same_bits(self, other: Self) -> bool {
+        self.0.same_bits(other.0) && self.1.same_bits(other.1) && self.2.same_bits(other.2)
+    }
+
     fn size_of() -> usize {
         X::size_of() + Y::size_of() + Z::size_of()
     }
@@ -276,6 +347,13 @@ impl<X: OnDevice, Y: OnDevice, Z: OnDevice, W: OnDevice> OnDevice for (X, Y, Z,
         )
     }
 
+    fn same_bits(self, other: Self) -> bool {
+        self.0.same_bits(other.0)
+            && self.1.same_bits(other.1)
+            && self.2.same_bits(other.2)
+            && self.3.same_bits(other.3)
+    }
+
     fn size_of() -> usize {
         X::size_of() + Y::size_of() + Z::size_of() + W::size_of()
     }
@@ -285,6 +363,30 @@ impl<X: OnDevice, Y: OnDevice, Z: OnDevice, W: OnDevice> OnDevice for (X, Y, Z,
     }
 }
 
+/// Two scalars packed into a single value like `.f16x2`, the first one in the lower half.
+impl<X: PtxScalar> OnDevice for [X; 2] {
+    const COMPONENTS: usize = 1;
+
+    fn write(self, buffers: &mut [Vec<u8>]) {
+        self[0].write(buffers);
+        self[1].write(buffers);
+    }
+
+    fn read(buffers: &[Vec<u8>], index: usize) -> Self {
+        unsafe {
+            buffers[0]
+                .as_ptr()
+                .cast::<Self>()
+                .add(index)
+                .read_unaligned()
+        }
+    }
+
+    fn same_bits(self, other: Self) -> bool {
+        self[0].same_bits(other[0]) && self[1].same_bits(other[1])
+    }
+}
+
 pub trait DebugRich {
     fn debug_rich(&self) -> String;
 }
@@ -299,6 +401,7 @@ macro_rules! impl_debug_rich {
     }
 }
 
+impl_debug_rich!(u8);
 impl_debug_rich!(u16);
 impl_debug_rich!(i16);
 impl_debug_rich!(u32);
@@ -312,6 +415,12 @@ impl DebugRich for f16 {
     }
 }
 
+impl DebugRich for bf16 {
+    fn debug_rich(&self) -> String {
+        format!("{self:#066b} {self:#X} {self:.24}")
+    }
+}
+
 impl DebugRich for f32 {
     fn debug_rich(&self) -> String {
         let bits = self.to_bits();
@@ -380,7 +489,259 @@ where
     }
 }
 
-pub trait PtxScalar: Copy + Num + Bounded + Debug + DebugRich + OnDevice + Any {
+impl<T: DebugRich> DebugRich for [T; 2] {
+    fn debug_rich(&self) -> String {
+        format!("[\n{},\n{},\n]", self[0].debug_rich(), self[1].debug_rich())
+    }
+}
+
+/// Coarse classification of test inputs, used to group mismatching values.
+pub trait InputClass {
+    fn input_class(&self) -> ClassKey;
+}
+
+/// Class of an input, one class per component of tuples. Made of static names, so that grouping
+/// mismatching values doesn't allocate.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClassKey {
+    components: [&'static str; 4],
+    len: usize,
+    tuple: bool,
+}
+
+impl ClassKey {
+    fn new(class: &'static str) -> Self {
+        ClassKey { components: [class, "", "", ""], len: 1, tuple: false }
+    }
+
+    fn tuple(keys: &[ClassKey]) -> Self {
+        let mut key = ClassKey { components: [""; 4], len: 0, tuple: true };
+        for component in keys.iter().flat_map(|k| &k.components[..k.len]) {
+            key.components[key.len] = component;
+            key.len += 1;
+        }
+        key
+    }
+}
+
+impl fmt::Display for ClassKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components = self.components[..self.len].join(", ");
+        if self.tuple {
+            write!(f, "({components})")
+        } else {
+            f.write_str(&components)
+        }
+    }
+}
+
+macro_rules! impl_input_class_int {
+    ($type:ident) => {
+        impl InputClass for $type {
+            fn input_class(&self) -> ClassKey {
+                ClassKey::new(match self.cmp(&0) {
+                    std::cmp::Ordering::Less => "negative",
+                    std::cmp::Ordering::Equal => "zero",
+                    std::cmp::Ordering::Greater => "positive",
+                })
+            }
+        }
+    }
+}
+
+impl_input_class_int!(u16);
+impl_input_class_int!(i16);
+impl_input_class_int!(u32);
+impl_input_class_int!(i32);
+impl_input_class_int!(u64);
+impl_input_class_int!(i64);
+
+fn float_class<T: Float>(value: T) -> ClassKey {
+    ClassKey::new(match value.classify() {
+        FpCategory::Nan => "nan",
+        FpCategory::Infinite => "inf",
+        FpCategory::Zero => "zero",
+        FpCategory::Subnormal => "subnormal",
+        FpCategory::Normal => "normal",
+    })
+}
+
+impl InputClass for f16 {
+    fn input_class(&self) -> ClassKey {
+        float_class(*self)
+    }
+}
+
+impl InputClass for bf16 {
+    fn input_class(&self) -> ClassKey {
+        float_class(*self)
+    }
+}
+
+impl InputClass for f32 {
+    fn input_class(&self) -> ClassKey {
+        float_class(*self)
+    }
+}
+
+impl InputClass for f64 {
+    fn input_class(&self) -> ClassKey {
+        float_class(*self)
+    }
+}
+
+impl<T: InputClass> InputClass for (T,) {
+    fn input_class(&self) -> ClassKey {
+        self.0.input_class()
+    }
+}
+
+impl<T1: InputClass, T2: InputClass> InputClass for (T1, T2) {
+    fn input_class(&self) -> ClassKey {
+        ClassKey::tuple(&[self.0.input_class(), self.1.input_class()])
+    }
+}
+
+impl<T1: InputClass, T2: InputClass, T3: InputClass> InputClass for (T1, T2, T3) {
+    fn input_class(&self) -> ClassKey {
+        ClassKey::tuple(&[
+            self.0.input_class(),
+            self.1.input_class(),
+            self.2.input_class(),
+        ])
+    }
+}
+
+impl<T1: InputClass, T2: InputClass, T3: InputClass, T4: InputClass> InputClass for (T1, T2, T3, T4) {
+    fn input_class(&self) -> ClassKey {
+        ClassKey::tuple(&[
+            self.0.input_class(),
+            self.1.input_class(),
+            self.2.input_class(),
+            self.3.input_class(),
+        ])
+    }
+}
+
+/// Class of the most unusual lane. Crossing the classes of both lanes would multiply the number of
+/// classes in mismatch summaries, especially when crossed again with other operands.
+impl<T: Float> InputClass for [T; 2] {
+    fn input_class(&self) -> ClassKey {
+        let rank = |value: &T| match value.classify() {
+            FpCategory::Normal => 0,
+            FpCategory::Zero => 1,
+            FpCategory::Subnormal => 2,
+            FpCategory::Infinite => 3,
+            FpCategory::Nan => 4,
+        };
+        float_class(*self.iter().max_by_key(|value| rank(value)).unwrap())
+    }
+}
+
+/// Values worth checking for every instruction, but unlikely to be hit by random inputs.
+pub trait EdgeCases: Sized {
+    fn edge_cases() -> Vec<Self>;
+}
+
+macro_rules! impl_edge_cases_int {
+    ($type:ty) => {
+        impl EdgeCases for $type {
+            fn edge_cases() -> Vec<Self> {
+                let bits = <$type>::BITS as $type;
+                let mut values = vec![
+                    0,
+                    1,
+                    2,
+                    (0 as $type).wrapping_sub(1),
+                    bits - 1,
+                    bits,
+                    bits + 1,
+                    <$type>::MIN,
+                    <$type>::MIN + 1,
+                    <$type>::MAX / 2,
+                    <$type>::MAX / 2 + 1,
+                    <$type>::MAX - 1,
+                    <$type>::MAX,
+                ];
+                values.sort();
+                values.dedup();
+                values
+            }
+        }
+    };
+}
+
+impl_edge_cases_int!(u16);
+impl_edge_cases_int!(i16);
+impl_edge_cases_int!(u32);
+impl_edge_cases_int!(i32);
+impl_edge_cases_int!(u64);
+impl_edge_cases_int!(i64);
+
+macro_rules! impl_edge_cases_float {
+    ($type:ty, $from_f64:expr) => {
+        impl EdgeCases for $type {
+            fn edge_cases() -> Vec<Self> {
+                let from_f64: fn(f64) -> $type = $from_f64;
+                let positive = [
+                    <$type>::from_bits(1),
+                    <$type>::from_bits(<$type>::MIN_POSITIVE.to_bits() - 1),
+                    <$type>::MIN_POSITIVE,
+                    from_f64(0.5),
+                    from_f64(1.0),
+                    from_f64(1.5),
+                    from_f64(2.0),
+                    <$type>::MAX,
+                    <$type>::INFINITY,
+                    <$type>::NAN,
+                ];
+                let mut values = vec![from_f64(0.0), from_f64(-0.0)];
+                for value in positive {
+                    values.push(value);
+                    values.push(-value);
+                }
+                values
+            }
+        }
+    };
+}
+
+impl_edge_cases_float!(f16, f16::from_f64);
+impl_edge_cases_float!(bf16, bf16::from_f64);
+impl_edge_cases_float!(f32, |x| x as f32);
+impl_edge_cases_float!(f64, |x| x);
+
+impl<X: EdgeCases + Copy, Y: EdgeCases + Copy> EdgeCases for (X, Y) {
+    fn edge_cases() -> Vec<Self> {
+        let ys = Y::edge_cases();
+        X::edge_cases().into_iter().flat_map(|x| ys.iter().map(move |&y| (x, y))).collect()
+    }
+}
+
+impl<X: EdgeCases + Copy, Y: EdgeCases + Copy, Z: EdgeCases + Copy> EdgeCases for (X, Y, Z) {
+    fn edge_cases() -> Vec<Self> {
+        <(X, (Y, Z))>::edge_cases().into_iter().map(|(x, (y, z))| (x, y, z)).collect()
+    }
+}
+
+impl<X: EdgeCases + Copy, Y: EdgeCases + Copy, Z: EdgeCases + Copy, W: EdgeCases + Copy> EdgeCases for (X, Y, Z, W) {
+    fn edge_cases() -> Vec<Self> {
+        <(X, (Y, (Z, W)))>::edge_cases().into_iter().map(|(x, (y, (z, w)))| (x, y, z, w)).collect()
+    }
+}
+
+/// Every edge case in both lanes, once with the same and once with another edge case in the other
+/// lane. All combinations would be too many for instructions with three packed operands.
+impl<X: EdgeCases + Copy> EdgeCases for [X; 2] {
+    fn edge_cases() -> Vec<Self> {
+        let xs = X::edge_cases();
+        let same = xs.iter().map(|&x| [x, x]);
+        let other = xs.iter().zip(xs.iter().rev()).map(|(&x, &y)| [x, y]);
+        same.chain(other).collect()
+    }
+}
+
+pub trait PtxScalar: Copy + Num + Bounded + Debug + DebugRich + InputClass + EdgeCases + OnDevice + Any {
     fn name() -> &'static str;
 
     fn unsigned() -> bool {
@@ -395,6 +756,10 @@ pub trait PtxScalar: Copy + Num + Bounded + Debug + DebugRich + OnDevice + Any {
     fn is_f32() -> bool {
         Self::float() && Self::size_of() == 4
     }
+    /// PTX has no registers of this type, values are kept in `.b16`/`.b32` registers instead
+    fn untyped_registers() -> bool {
+        false
+    }
 }
 
 impl PtxScalar for u16 {
@@ -442,6 +807,18 @@ impl PtxScalar for f16 {
     }
 }
 
+impl PtxScalar for bf16 {
+    fn name() -> &'static str {
+        "bf16"
+    }
+    fn float() -> bool {
+        true
+    }
+    fn untyped_registers() -> bool {
+        true
+    }
+}
+
 impl PtxScalar for f32 {
     fn name() -> &'static str {
         "f32"
@@ -460,282 +837,740 @@ impl PtxScalar for f64 {
     }
 }
 
-const SEED: u64 = 0x761194f3027874ef;
+/// Default seed of random inputs
+pub const SEED: u64 = 0x761194f3027874ef;
+/// Default number of random inputs of every random test
+pub const RANDOM_COUNT: usize = 1 << 32;
 const GROUP_SIZE: usize = 128;
 // Totally unscientific number that works on my machine
 const SAFE_MEMORY_LIMIT: usize = 1 << 29;
+/// Number of batches in flight, each one with its own stream and buffers
+const PIPELINE_DEPTH: usize = 2;
+fn load_module<'a>(cuda: &'a Cuda, src: &CStr) -> Result<Module<'a>, TestError> {
+    Module::load(cuda, src).map_err(|LoadError { error, log }| {
+        let error = format!("CUDA error: {error}");
+        let log = format!("JIT compilation produced the following log:\n{log}");
+        let source = format!("Program source:\n{}", common::numbered_lines(&src.to_string_lossy()));
+        TestError::CompilationFail { message: format!("{error}\n{log}\n{source}") }
+    })
+}
 
-fn load_module(ctx: &dyn TestContext, t: &dyn TestPtx) -> Result<CUmodule, TestError> {
-    let cuda = ctx.cuda();
+/// Pinned host buffer and device buffer of the same size.
+struct Buffer<'a> {
+    host: HostBuffer<'a, u8>,
+    device: DeviceBuffer<'a, u8>,
+}
 
-    match ctx.prepare_test_source(t) {
-        Ok(src) => {
-            let mut module = ptr::null_mut();
-            let load_result = unsafe { cuda.cuModuleLoadData(&mut module, src.as_ptr() as _) };
+impl<'a> Buffer<'a> {
+    fn alloc(cuda: &'a Cuda, size: usize) -> Result<Self, CudaError> {
+        Ok(Buffer { host: HostBuffer::alloc(cuda, size)?, device: DeviceBuffer::alloc(cuda, size)? })
+    }
 
-            match load_result {
-                Ok(()) => Ok(module),
-                Err(code) => return Err(TestError::CompilationFail { message: format!("CUDA Error {code}") }),
-            }
-        },
-        Err(message) => return Err(TestError::CompilationFail { message }),
+    /// Make sure both buffers hold at least `size` bytes, previous contents are lost.
+    fn reserve(&mut self, cuda: &'a Cuda, size: usize) -> Result<(), CudaError> {
+        if self.host.len() < size {
+            // Free the old buffers first, they may take up a good part of device memory
+            *self = Buffer::alloc(cuda, 0)?;
+            *self = Buffer::alloc(cuda, size)?;
+        }
+        Ok(())
     }
 }
 
-pub fn run_random<T: RandomTest>(ctx: &dyn TestContext) -> Result<(), TestError> {
-    let cuda = ctx.cuda();
-    let t =  T::default();
+/// Stream and buffers of a batch in flight.
+struct Slot<'a> {
+    stream: Stream<'a>,
+    inputs: Vec<Buffer<'a>>,
+    outputs: Vec<Buffer<'a>>,
+    count: usize,
+}
+
+/// Test kernel loaded into one of the CUDA libraries.
+struct Kernel<'a> {
+    cuda: &'a Cuda,
+    module: Module<'a>,
+}
 
-    let module = load_module(ctx, &t)?;
-    let mut kernel = ptr::null_mut();
-    unsafe { cuda.cuModuleGetFunction(&mut kernel, module, c"run".as_ptr()) }.unwrap();
+impl<'a> Kernel<'a> {
+    const NAME: &'static CStr = c"run";
 
-    let mut rng = XorShiftRng::seed_from_u64(SEED);
-    let mut free_memory = 0;
-    let mut total_memory = 0;
-    unsafe { cuda.cuMemGetInfo_v2(&mut free_memory, &mut total_memory) }.unwrap();
-    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT);
-    let total_elements = 2.pow(32);
-    assert!(total_elements % GROUP_SIZE == 0);
-    let element_size = T::Input::size_of() + T::Output::size_of();
+    fn load(cuda: &'a Cuda, src: &CStr) -> Result<Self, TestError> {
+        let module = load_module(cuda, src)?;
+        module.function(Self::NAME)?;
+        Ok(Kernel { cuda, module })
+    }
+
+    /// Create `PIPELINE_DEPTH` slots for batches with `inputs` and `outputs` components.
+    fn create_slots(&self, inputs: usize, outputs: usize) -> Result<Vec<Slot<'a>>, CudaError> {
+        let buffers = |count| (0..count).map(|_| Buffer::alloc(self.cuda, 0)).collect::<Result<Vec<_>, _>>();
+        (0..PIPELINE_DEPTH)
+            .map(|_| Ok(Slot { stream: Stream::create(self.cuda)?, inputs: buffers(inputs)?, outputs: buffers(outputs)?, count: 0 }))
+            .collect()
+    }
+
+    /// Queue copying a batch of inputs to the device, running the kernel on them and copying
+    /// results back on the stream of `slot`. Returns without waiting for the device.
+    fn submit(&self, slot: &mut Slot<'a>, inputs: &[Vec<u8>], output_sizes: &[usize], count: usize) -> Result<(), CudaError> {
+        for (buffer, input) in slot.inputs.iter_mut().zip(inputs) {
+            buffer.reserve(self.cuda, input.len())?;
+            buffer.host.as_mut_slice()[..input.len()].copy_from_slice(input);
+            unsafe { buffer.device.copy_from_host_async(&buffer.host, input.len(), &slot.stream) }?;
+        }
+        for (buffer, size) in slot.outputs.iter_mut().zip(output_sizes) {
+            buffer.reserve(self.cuda, count * size)?;
+        }
+        let params: Vec<_> = slot.inputs.iter().chain(&slot.outputs).map(|buffer| buffer.device.ptr()).collect();
+        let function = self.module.function(Self::NAME)?;
+        unsafe { function.launch(&slot.stream, (count / GROUP_SIZE) as u32, GROUP_SIZE as u32, &params) }?;
+        for (buffer, size) in slot.outputs.iter_mut().zip(output_sizes) {
+            unsafe { buffer.device.copy_to_host_async(&mut buffer.host, count * size, &slot.stream) }?;
+        }
+        slot.count = count;
+        Ok(())
+    }
+
+    /// Wait for the batch queued on `slot` and copy its results into `outputs`, one buffer per
+    /// output component.
+    fn wait(&self, slot: &Slot, output_sizes: &[usize], outputs: &mut [Vec<u8>]) -> Result<(), CudaError> {
+        slot.stream.synchronize()?;
+        for ((output, buffer), size) in outputs.iter_mut().zip(&slot.outputs).zip(output_sizes) {
+            output.clear();
+            output.extend_from_slice(&buffer.host.as_slice()[..slot.count * size]);
+        }
+        Ok(())
+    }
+}
+
+/// Load the test kernel into the tested CUDA library and, in differential mode, into the reference one.
+/// Returns the source of the module together with the kernels.
+fn load_kernels<'a>(
+    ctx: &'a dyn TestContext,
+    name: &str,
+    t: &dyn TestPtx,
+) -> Result<(CString, Kernel<'a>, Option<Kernel<'a>>), TestError> {
+    let src = ctx
+        .prepare_test_source(name, t)
+        .map_err(|message| TestError::CompilationFail { message })?;
+    let kernel = Kernel::load(ctx.cuda(), &src)?;
+    let reference = match ctx.reference_cuda().map(|cuda| Kernel::load(cuda, &src)).transpose() {
+        Ok(reference) => reference,
+        Err(TestError::CompilationFail { message }) => {
+            let message = format!("Reference CUDA library failed compilation: {message}");
+            return Err(TestError::CompilationFail { message });
+        }
+        Err(e) => return Err(e),
+    };
+    Ok((src, kernel, reference))
+}
+
+/// Check the first `count` outputs of a batch against the host reference implementation or, in
+/// differential mode, bit for bit against outputs produced by the reference CUDA library.
+///
+/// The batch is split into contiguous chunks verified on all available cores. Host reference
+/// implementations may change the rounding mode, which is a part of the per-thread floating point
+/// environment, so workers don't affect each other.
+fn verify<T: TestCommon>(
+    t: &T,
+    count: usize,
+    inputs: &[Vec<u8>],
+    outputs: &[Vec<u8>],
+    reference_outputs: Option<&[Vec<u8>]>,
+    mismatches: &mut MismatchSummary,
+) {
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = count.div_ceil(threads).max(1);
+    let verify_chunk = |start: usize, end: usize| {
+        let mut mismatches = MismatchSummary::default();
+        for i in start..end {
+            let input = T::Input::read(inputs, i);
+            let output = T::Output::read(outputs, i);
+            match reference_outputs {
+                Some(reference_outputs) => {
+                    let expected = T::Output::read(reference_outputs, i);
+                    if !output.same_bits(expected) {
+                        mismatches.record(input, output, expected);
+                    }
+                }
+                None => {
+                    if let Err(expected) = t.host_verify(input, output) {
+                        mismatches.record(input, output, expected);
+                    }
+                }
+            }
+        }
+        mismatches
+    };
+    let chunks = thread::scope(|scope| {
+        let workers: Vec<_> = (0..count)
+            .step_by(chunk_size)
+            .map(|start| {
+                let end = (start + chunk_size).min(count);
+                scope.spawn(move || verify_chunk(start, end))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect::<Vec<_>>()
+    });
+    // Chunks are merged in order, so examples are still the first mismatching values
+    for chunk in chunks {
+        mismatches.merge(chunk);
+    }
+}
+
+fn next_multiple_of(value: usize, multiple: usize) -> usize {
+    ((value + multiple - 1) / multiple) * multiple
+}
+
+/// Split `total_elements` into batches, in elements, small enough to fit into device memory.
+fn batch_sizes(cuda: &Cuda, total_elements: usize, element_size: usize) -> Result<Vec<usize>, CudaError> {
+    let (_, total_memory) = cuda.mem_info()?;
+    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT) / PIPELINE_DEPTH;
     let required_memory = total_elements * element_size;
     let iterations = (required_memory / max_memory).max(1);
     let memory_batch_size: usize =
         next_multiple_of(required_memory / iterations, GROUP_SIZE * element_size);
-    let mut inputs = vec![Vec::new(); T::Input::COMPONENTS];
-    let mut outputs = vec![T::Output::zero(); memory_batch_size / element_size];
-    for iteration in 0..iterations {
-        assert_eq!(T::Output::COMPONENTS, 1);
-        let memory_batch_size = if iteration == iterations - 1 {
-            required_memory - (memory_batch_size * (iterations - 1))
+    Ok((0..iterations)
+        .map(|iteration| {
+            let memory_batch_size = if iteration == iterations - 1 {
+                required_memory - (memory_batch_size * (iterations - 1))
+            } else {
+                memory_batch_size
+            };
+            memory_batch_size / element_size
+        })
+        .collect())
+}
+
+/// Size in bytes of every component of `V`, each one is stored in a separate buffer.
+fn component_sizes<V: OnDevice>() -> Vec<usize> {
+    let mut buffers = vec![Vec::new(); V::COMPONENTS];
+    V::zero().write(&mut buffers);
+    buffers.iter().map(Vec::len).collect()
+}
+
+/// Fill `inputs` with the next `count` inputs from `source`, starting at index `start`.
+///
+/// Batches are padded to a multiple of `GROUP_SIZE` by repeating the last input of the source,
+/// outputs computed for the padding are never verified.
+fn generate_batch<T: TestCommon>(
+    t: &T,
+    source: &mut dyn InputSource<T>,
+    start: usize,
+    count: usize,
+    last: &mut Option<T::Input>,
+    inputs: &mut [Vec<u8>],
+) {
+    for vec in inputs.iter_mut() {
+        vec.clear();
+    }
+    for index in start..start + count {
+        let input = if index < source.count() {
+            *last.insert(source.next(t, index))
         } else {
-            memory_batch_size
+            last.expect("padding follows at least one input")
         };
-        let element_batch_size = memory_batch_size / element_size;
-        for vec in inputs.iter_mut() {
-            vec.clear();
-        }
-        for _ in 0..element_batch_size {
-            T::generate(&mut rng).write(&mut inputs);
-        }
-        outputs.resize(element_batch_size, T::Output::zero());
-        let dev_inputs: Vec<u64> = inputs
-            .iter()
-            .map(|vec| {
-                let mut devptr = 0;
-                unsafe { cuda.cuMemAlloc_v2(&mut devptr, vec.len()) }.unwrap();
-                unsafe { cuda.cuMemcpyHtoD_v2(devptr, vec.as_ptr().cast_mut().cast(), vec.len()) }
-                    .unwrap();
-                devptr
-            })
-            .collect();
-        let mut dev_output = 0;
-        unsafe { cuda.cuMemAlloc_v2(&mut dev_output, element_batch_size * T::Output::size_of()) }
-            .unwrap();
-        let mut args = dev_inputs
-            .iter()
-            .map(|ptr| ptr as *const u64)
-            .collect::<Vec<_>>();
-        args.push(&dev_output);
-        unsafe {
-            cuda.cuLaunchKernel(
-                kernel,
-                (element_batch_size / GROUP_SIZE) as u32,
-                1,
-                1,
-                GROUP_SIZE as u32,
-                1,
-                1,
-                0,
-                0 as _,
-                args.as_mut_ptr() as _,
-                ptr::null_mut(),
-            )
-        }
-        .unwrap();
-        unsafe { cuda.cuStreamSynchronize(0 as _) }.unwrap();
-        unsafe {
-            cuda.cuMemcpyDtoH_v2(
-                outputs.as_mut_ptr() as _,
-                dev_output,
-                outputs.len() * T::Output::size_of(),
-            )
-        }
-        .unwrap();
-        for (i, output) in outputs.iter().copied().enumerate() {
-            let input = T::Input::read(&inputs, i);
-            if let Err(expected) = t.host_verify(input, output) {
-                return Err(TestError::ResultMismatch {
-                    input: input.debug_rich(),
-                    output: output.debug_rich(),
-                    expected: expected.debug_rich(),
-                });
+        input.write(inputs);
+    }
+}
+
+/// Run the test over all inputs of `source` in batches and verify its outputs.
+///
+/// Depending on the context outputs are computed by the tested CUDA library, optionally
+/// recorded into a golden file, or read back from a golden file without touching CUDA at all.
+fn run<T: TestCommon>(
+    ctx: &dyn TestContext,
+    name: &str,
+    t: &T,
+    source: &mut dyn InputSource<T>,
+) -> Result<(), TestError> {
+    let total_elements = next_multiple_of(source.count(), GROUP_SIZE);
+    let output_sizes = component_sizes::<T::Output>();
+    let mut inputs = vec![Vec::new(); T::Input::COMPONENTS];
+    let mut outputs = vec![Vec::new(); T::Output::COMPONENTS];
+    let mut reference_outputs = vec![Vec::new(); T::Output::COMPONENTS];
+    let mut last = None;
+    let mut mismatches = MismatchSummary::default();
+    let mut start = 0;
+    let mut verified = 0;
+    let started = Instant::now();
+    let deadline = ctx.time_limit().deadline(started);
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    if let Some(Golden::Replay(dir)) = ctx.golden() {
+        let mut replay = match Replay::open(dir, name).unwrap_or_else(|e| panic!("Failed to replay {name}: {e}")) {
+            Recorded::CompilationFail { message } => return Err(TestError::CompilationFail { message }),
+            Recorded::Outputs(replay) => replay,
+        };
+        if replay.output_sizes() != output_sizes {
+            panic!("Failed to replay {name}: recorded outputs have a different layout");
+        }
+        source.reseed(replay.seed());
+        while let Some(count) = replay.next_batch(&mut outputs).unwrap_or_else(|e| panic!("Failed to replay {name}: {e}")) {
+            generate_batch(t, source, start, count, &mut last, &mut inputs);
+            let valid = count.min(source.count().saturating_sub(start));
+            verify(t, valid, &inputs, &outputs, None, &mut mismatches);
+            start += count;
+            verified += valid;
+            if verified < source.count() && expired() {
+                return mismatches.into_result_partial(started, verified, source.count());
+            }
+        }
+        if start != total_elements {
+            panic!("Failed to replay {name}: recorded outputs cover {start} inputs instead of {total_elements}, check --random-count");
+        }
+        return mismatches.into_result();
+    }
+
+    let record_dir = match ctx.golden() {
+        Some(Golden::Record(dir)) => Some(dir),
+        _ => None,
+    };
+    let (src, kernel, reference) = match load_kernels(ctx, name, t) {
+        Ok(kernels) => kernels,
+        Err(TestError::CompilationFail { message }) => {
+            if let Some(dir) = record_dir {
+                golden::record_compilation_fail(dir, name, &message).unwrap_or_else(|e| panic!("Failed to record {name}: {e}"));
+            }
+            return Err(TestError::CompilationFail { message });
+        }
+        Err(e) => return Err(e),
+    };
+    let mut recorder = record_dir.map(|dir| {
+        Recorder::create(dir, name, source.seed(), &output_sizes).unwrap_or_else(|e| panic!("Failed to record {name}: {e}"))
+    });
+    mismatches.reference = reference.is_some();
+
+    let element_size = T::Input::size_of() + T::Output::size_of();
+    let batches = if total_elements > 0 {
+        batch_sizes(ctx.cuda(), total_elements, element_size)?
+    } else {
+        Vec::new()
+    };
+    let mut slots = kernel.create_slots(T::Input::COMPONENTS, T::Output::COMPONENTS)?;
+    let mut reference = match reference {
+        Some(reference) => {
+            let slots = reference.create_slots(T::Input::COMPONENTS, T::Output::COMPONENTS)?;
+            Some((reference, slots))
+        }
+        None => None,
+    };
+    let mut slot_inputs = vec![inputs; PIPELINE_DEPTH];
+    let mut submitted = 0;
+    for (batch, &element_batch_size) in batches.iter().enumerate() {
+        // Generate and queue the following batches while the device works on this one
+        while submitted < batches.len() && submitted < batch + PIPELINE_DEPTH && !expired() {
+            let slot = submitted % PIPELINE_DEPTH;
+            let count = batches[submitted];
+            let inputs = &mut slot_inputs[slot];
+            generate_batch(t, source, start, count, &mut last, inputs);
+            kernel.submit(&mut slots[slot], inputs, &output_sizes, count)?;
+            if let Some((reference, reference_slots)) = &mut reference {
+                reference.submit(&mut reference_slots[slot], inputs, &output_sizes, count)?;
             }
+            start += count;
+            submitted += 1;
+        }
+        // Out of time, batches already in flight were verified
+        if batch == submitted {
+            break;
+        }
+        let slot = batch % PIPELINE_DEPTH;
+        kernel.wait(&slots[slot], &output_sizes, &mut outputs)?;
+        if let Some(recorder) = &mut recorder {
+            recorder.batch(element_batch_size, &outputs).unwrap_or_else(|e| panic!("Failed to record {name}: {e}"));
         }
-        for devptr in dev_inputs {
-            unsafe { cuda.cuMemFree_v2(devptr) }.unwrap();
+        if let Some((reference, reference_slots)) = &reference {
+            reference.wait(&reference_slots[slot], &output_sizes, &mut reference_outputs)?;
         }
-        unsafe { cuda.cuMemFree_v2(dev_output) }.unwrap();
+        let reference_outputs = reference.as_ref().map(|_| &reference_outputs[..]);
+        let valid = element_batch_size.min(source.count() - verified);
+        verify(t, valid, &slot_inputs[slot], &outputs, reference_outputs, &mut mismatches);
+        verified += valid;
+    }
+    if let (Some(dir), Some(bits)) = (ctx.repro_dir(), &mismatches.first) {
+        let path = repro::write(dir, name, &src, &mismatches, bits)
+            .unwrap_or_else(|e| panic!("Failed to write reproducer of {name}: {e}"));
+        mismatches.reproducer = Some(path);
+    }
+    if verified < source.count() {
+        // Golden files always cover all inputs, a partial one would fail to replay
+        if let Some(recorder) = recorder {
+            recorder.discard().unwrap_or_else(|e| panic!("Failed to record {name}: {e}"));
+        }
+        return mismatches.into_result_partial(started, verified, source.count());
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish().unwrap_or_else(|e| panic!("Failed to record {name}: {e}"));
     }
-    unsafe { cuda.cuModuleUnload(module) }.unwrap();
 
-    Ok(())
+    mismatches.into_result()
 }
 
-fn next_multiple_of(value: usize, multiple: usize) -> usize {
-    ((value + multiple - 1) / multiple) * multiple
+/// Produces inputs of a test, in order of their indices.
+pub trait InputSource<T: TestCommon> {
+    /// Total number of inputs
+    fn count(&self) -> usize;
+    /// Produce the input at `index`, indices are requested in increasing order
+    fn next(&mut self, t: &T, index: usize) -> T::Input;
+    /// Seed of random inputs, recorded into golden files
+    fn seed(&self) -> u64 {
+        0
+    }
+    /// Start over with a different seed, for replaying recorded outputs
+    fn reseed(&mut self, _seed: u64) {}
 }
 
-pub fn run_range<Test: RangeTest>(ctx: &dyn TestContext, t: Test) -> Result<(), TestError> {
-    let cuda = ctx.cuda();
+/// All inputs from `0` to `RangeTest::MAX_VALUE`.
+pub struct RangeInputs;
 
-    let module = load_module(ctx, &t)?;
-    let mut kernel = ptr::null_mut();
-    unsafe { cuda.cuModuleGetFunction(&mut kernel, module, c"run".as_ptr()) }.unwrap();
+impl<T: RangeTest> InputSource<T> for RangeInputs {
+    fn count(&self) -> usize {
+        T::MAX_VALUE as usize + 1
+    }
 
-    let mut free_memory = 0;
-    let mut total_memory = 0;
-    unsafe { cuda.cuMemGetInfo_v2(&mut free_memory, &mut total_memory) }.unwrap();
-    let max_memory = (total_memory / 2).min(SAFE_MEMORY_LIMIT);
-    let total_elements = Test::MAX_VALUE as usize + 1;
-    if total_elements % GROUP_SIZE != 0 {
-        panic!(
-            "Total element count {} must be divisble by {}",
-            total_elements, GROUP_SIZE
-        );
+    fn next(&mut self, t: &T, index: usize) -> T::Input {
+        t.generate(index as u32)
     }
-    let element_size = Test::Input::size_of() + Test::Output::size_of();
-    let required_memory = total_elements * element_size;
-    let iterations = (required_memory / max_memory).max(1);
-    let memory_batch_size: usize =
-        next_multiple_of(required_memory / iterations, GROUP_SIZE * element_size);
-    let mut inputs = vec![Vec::new(); Test::Input::COMPONENTS];
-    let mut outputs = vec![Test::Output::zero(); memory_batch_size / element_size];
-    for iteration in 0..iterations {
-        assert_eq!(Test::Output::COMPONENTS, 1);
-        let elment_start = iteration * memory_batch_size / element_size;
-        let memory_batch_size = if iteration == iterations - 1 {
-            required_memory - (memory_batch_size * (iterations - 1))
-        } else {
-            memory_batch_size
+}
+
+/// Inputs generated by `RandomTest::generate` from a seeded RNG.
+pub struct RandomInputs {
+    seed: u64,
+    rng: XorShiftRng,
+    count: usize,
+}
+
+impl RandomInputs {
+    pub fn new(seed: u64, count: usize) -> Self {
+        RandomInputs { seed, rng: XorShiftRng::seed_from_u64(seed), count }
+    }
+}
+
+impl<T: RandomTest> InputSource<T> for RandomInputs {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn next(&mut self, _t: &T, _index: usize) -> T::Input {
+        T::generate(&mut self.rng)
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        *self = RandomInputs::new(seed, self.count);
+    }
+}
+
+/// Seed of random inputs as requested on the command line, either a number (decimal or
+/// hexadecimal with a `0x` prefix) or `random` for a new seed on every run.
+#[derive(Debug, Clone, Copy)]
+pub enum Seed {
+    Fixed(u64),
+    PerRun,
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "random" {
+            return Ok(Seed::PerRun);
+        }
+        let seed = match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => s.parse(),
         };
-        let element_batch_size = memory_batch_size / element_size;
-        for vec in inputs.iter_mut() {
-            vec.clear();
-        }
-        for i in 0..element_batch_size {
-            let input = t.generate((elment_start + i) as u32);
-            input.write(&mut inputs);
-        }
-        outputs.resize(element_batch_size, Test::Output::zero());
-        let dev_inputs: Vec<u64> = inputs
-            .iter()
-            .map(|vec| {
-                let mut devptr = 0;
-                unsafe { cuda.cuMemAlloc_v2(&mut devptr, vec.len()) }.unwrap();
-                unsafe { cuda.cuMemcpyHtoD_v2(devptr, vec.as_ptr().cast_mut().cast(), vec.len()) }
-                    .unwrap();
-                devptr
-            })
-            .collect();
-        let mut dev_output = 0;
-        unsafe {
-            cuda.cuMemAlloc_v2(
-                &mut dev_output,
-                element_batch_size * Test::Output::size_of(),
-            )
-        }
-        .unwrap();
-        let mut args = dev_inputs
-            .iter()
-            .map(|ptr| ptr as *const u64)
-            .collect::<Vec<_>>();
-        args.push(&dev_output);
-        unsafe {
-            cuda.cuLaunchKernel(
-                kernel,
-                (element_batch_size / GROUP_SIZE) as u32,
-                1,
-                1,
-                GROUP_SIZE as u32,
-                1,
-                1,
-                0,
-                0 as _,
-                args.as_mut_ptr() as _,
-                ptr::null_mut(),
-            )
-        }
-        .unwrap();
-        unsafe { cuda.cuStreamSynchronize(0 as _) }.unwrap();
-        unsafe {
-            cuda.cuMemcpyDtoH_v2(
-                outputs.as_mut_ptr() as _,
-                dev_output,
-                outputs.len() * Test::Output::size_of(),
-            )
-        }
-        .unwrap();
-        for (i, output) in outputs.iter().copied().enumerate() {
-            let input = Test::Input::read(&inputs, i);
-            if let Err(expected) = t.host_verify(input, output) {
-                return Err(TestError::ResultMismatch {
-                    input: input.debug_rich(),
-                    output: output.debug_rich(),
-                    expected: expected.debug_rich(),
-                });
+        seed.map(Seed::Fixed).map_err(|e| format!("invalid seed '{s}': {e}"))
+    }
+}
+
+/// Fixed list of inputs, e.g. edge cases or a corpus file.
+pub struct ListInputs<I> {
+    inputs: Vec<I>,
+}
+
+impl<I: EdgeCases> ListInputs<I> {
+    pub fn edge_cases() -> Self {
+        ListInputs { inputs: I::edge_cases() }
+    }
+}
+
+impl<I: OnDevice> ListInputs<I> {
+    /// Read inputs from a corpus file. Every line holds a single input, its components are
+    /// separated by whitespace and written as hexadecimal bit patterns, e.g. `0x3f800000 0x1f`.
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn from_corpus(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let sizes = component_sizes::<I>();
+        let mut buffers = vec![Vec::new(); I::COMPONENTS];
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("{}:{}: {message}", path.display(), line_index + 1);
+            let components: Vec<_> = line.split_whitespace().collect();
+            if components.len() != I::COMPONENTS {
+                return Err(error(&format!("expected {} components", I::COMPONENTS)));
+            }
+            for ((component, size), buffer) in components.iter().zip(&sizes).zip(&mut buffers) {
+                let digits = component.strip_prefix("0x").unwrap_or(component);
+                let bits = u64::from_str_radix(digits, 16).map_err(|e| error(&e.to_string()))?;
+                if *size < 8 && bits >> (size * 8) != 0 {
+                    return Err(error(&format!("{component} doesn't fit into {size} bytes")));
+                }
+                buffer.extend_from_slice(&bits.to_le_bytes()[..*size]);
             }
         }
-        for devptr in dev_inputs {
-            unsafe { cuda.cuMemFree_v2(devptr) }.unwrap();
+        let count = buffers[0].len() / sizes[0];
+        let inputs = (0..count).map(|i| I::read(&buffers, i)).collect();
+        Ok(ListInputs { inputs })
+    }
+}
+
+impl<T: TestCommon> InputSource<T> for ListInputs<T::Input> {
+    fn count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn next(&mut self, _t: &T, index: usize) -> T::Input {
+        self.inputs[index]
+    }
+}
+
+/// All inputs of the first source followed by all inputs of the second one.
+pub struct ChainInputs<A, B>(pub A, pub B);
+
+impl<T: TestCommon, A: InputSource<T>, B: InputSource<T>> InputSource<T> for ChainInputs<A, B> {
+    fn count(&self) -> usize {
+        self.0.count() + self.1.count()
+    }
+
+    fn next(&mut self, t: &T, index: usize) -> T::Input {
+        if index < self.0.count() {
+            self.0.next(t, index)
+        } else {
+            self.1.next(t, index - self.0.count())
+        }
+    }
+
+    fn seed(&self) -> u64 {
+        self.1.seed()
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.1.reseed(seed);
+    }
+}
+
+/// Run the test on its default inputs, or on inputs from its corpus file when the context has one.
+fn run_with_corpus<T: TestCommon>(
+    ctx: &dyn TestContext,
+    name: &str,
+    t: &T,
+    source: &mut dyn InputSource<T>,
+) -> Result<(), TestError> {
+    let corpus = ctx.corpus().map(|dir| dir.join(format!("{name}.txt"))).filter(|path| path.exists());
+    match corpus {
+        Some(path) => {
+            let mut corpus = ListInputs::<T::Input>::from_corpus(&path).unwrap_or_else(|e| panic!("Failed to read corpus: {e}"));
+            run(ctx, name, t, &mut corpus)
+        }
+        None => run(ctx, name, t, source),
+    }
+}
+
+pub type TestFunction = Box<dyn FnOnce(&dyn TestContext, &str) -> Result<(), TestError>>;
+
+/// What a test covers, for listing tests without running them.
+pub struct TestInfo {
+    /// Host type of inputs, e.g. `(f16, f16)`
+    pub input: String,
+    /// Host type of outputs
+    pub output: String,
+    /// Number of inputs tested regardless of `--random-count`, all inputs of exhaustive tests and
+    /// edge cases of random tests
+    pub fixed_inputs: usize,
+    /// Parameters of the kernel, as returned by `TestPtx::args`
+    pub args: Vec<String>,
+    /// Body of the kernel, as returned by `TestPtx::body`
+    pub body: String,
+    /// Named variants of tests made of several kernels, which have no kernel of their own
+    pub variants: Vec<(String, TestInfo)>,
+}
+
+impl TestInfo {
+    fn new<T: TestCommon>(t: &T, fixed_inputs: usize) -> Self {
+        TestInfo {
+            input: short_type_name::<T::Input>(),
+            output: short_type_name::<T::Output>(),
+            fixed_inputs,
+            args: t.args().iter().map(|arg| arg.to_string()).collect(),
+            body: t.body(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// All kernels of the test `name` with their names, the test itself or its variants.
+    pub fn kernels<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a TestInfo)> {
+        if self.variants.is_empty() {
+            vec![(name, self)]
+        } else {
+            self.variants.iter().map(|(name, info)| (name.as_str(), info)).collect()
         }
-        unsafe { cuda.cuMemFree_v2(dev_output) }.unwrap();
     }
-    unsafe { cuda.cuModuleUnload(module) }.unwrap();
+}
 
-    Ok(())
+/// Name of a type without module paths, e.g. `(f16, u32)` instead of `(half::binary16::f16, u32)`.
+fn short_type_name<T>() -> String {
+    // Compiled once, names are shortened twice per test every time tests are listed
+    static MODULE_PATH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w+::").unwrap());
+    MODULE_PATH.replace_all(any::type_name::<T>(), "").into_owned()
 }
 
-pub type TestFunction = Box<dyn FnOnce(&dyn TestContext) -> Result<(), TestError>>;
+/// A test ready to run, together with a description of what it covers.
+pub struct Test {
+    pub run: TestFunction,
+    pub info: TestInfo,
+}
+
+pub fn make_random<T: RandomTest + 'static>(t: T) -> Test
+where
+    T::Input: EdgeCases,
+{
+    let info = TestInfo::new(&t, T::Input::edge_cases().len());
+    let run = Box::new(move |ctx: &dyn TestContext, name: &str| {
+        let random = RandomInputs::new(ctx.seed(), ctx.random_count());
+        let mut source = ChainInputs(ListInputs::edge_cases(), random);
+        run_with_corpus::<T>(ctx, name, &t, &mut source)
+    });
+    Test { run, info }
+}
+
+pub fn make_range<T: RangeTest + 'static>(t: T) -> Test {
+    let info = TestInfo::new(&t, T::MAX_VALUE as usize + 1);
+    let run = Box::new(move |ctx: &dyn TestContext, name: &str| run_with_corpus::<T>(ctx, name, &t, &mut RangeInputs));
+    Test { run, info }
+}
+
+/// How a test picks its inputs.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Inputs {
+    /// All possible inputs, see `RangeTest`
+    #[default]
+    Exhaustive,
+    /// Edge cases followed by random inputs, see `RandomTest`
+    Random,
+    /// No inputs, the test only checks that invalid instruction variants fail to compile
+    InvalidCompile,
+}
+
+impl Inputs {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Inputs::Exhaustive => "exhaustive",
+            Inputs::Random => "random",
+            Inputs::InvalidCompile => "invalid",
+        }
+    }
+}
 
-pub fn make_random<T: RandomTest>() -> TestFunction {
-    return Box::new(|ctx| run_random::<T>(ctx));
+/// Structured properties of a test, used to select tests with `--tag`.
+#[derive(Clone, Default)]
+pub struct Tags {
+    /// PTX instruction, e.g. `cvt`
+    pub instruction: &'static str,
+    /// PTX types of the instruction, e.g. `f16` and `f32` for `cvt.rn.f16.f32`
+    pub types: Vec<String>,
+    /// Rounding or approximation mode, e.g. `rn` or `approx`
+    pub rounding: Option<&'static str>,
+    pub ftz: bool,
+    pub sat: bool,
+    /// Other modifiers of the instruction, e.g. the comparison of `setp`
+    pub modifiers: Vec<&'static str>,
+    pub inputs: Inputs,
 }
 
-pub fn make_range<T: RangeTest + 'static>(t: T) -> TestFunction {
-    return Box::new(move |ctx| run_range::<T>(ctx, t));
+impl Tags {
+    /// All tags as accepted by `--tag`.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![self.instruction];
+        names.extend(self.types.iter().map(String::as_str));
+        names.extend(self.rounding);
+        if self.ftz {
+            names.push("ftz");
+        }
+        if self.sat {
+            names.push("sat");
+        }
+        names.extend(&self.modifiers);
+        names.push(self.inputs.as_str());
+        names
+    }
 }
 
 pub struct TestCase {
     pub test: TestFunction,
     pub name: String,
+    pub tags: Tags,
+    pub info: TestInfo,
 }
 
 impl TestCase {
-    pub fn new(name: String, test: TestFunction) -> Self {
-        TestCase { test, name }
+    pub fn new(name: String, tags: Tags, test: Test) -> Self {
+        TestCase { test: test.run, name, tags, info: test.info }
+    }
+
+    /// Number of inputs the test covers when run with `random_count` random inputs.
+    pub fn input_count(&self, random_count: usize) -> usize {
+        match self.tags.inputs {
+            Inputs::Random => self.info.fixed_inputs + random_count,
+            Inputs::Exhaustive | Inputs::InvalidCompile => self.info.fixed_inputs,
+        }
     }
 
     pub fn join_invalid_tests(
         name: String,
+        instruction: &'static str,
         tests: Vec<(
             String,
-            TestFunction,
+            Test,
         )>,
     ) -> Self {
         use TestError::*;
 
-        let test = Box::new(move |ctx: &dyn TestContext| {
-            for (name, test) in tests {
-                match test(ctx) {
+        let mut runs = Vec::new();
+        let mut variants = Vec::new();
+        for (name, test) in tests {
+            runs.push((name.clone(), test.run));
+            variants.push((name, test.info));
+        }
+        let info = TestInfo {
+            input: String::new(),
+            output: String::new(),
+            fixed_inputs: 0,
+            args: Vec::new(),
+            body: String::new(),
+            variants,
+        };
+        let test = Box::new(move |ctx: &dyn TestContext, _: &str| {
+            for (name, test) in runs {
+                match test(ctx, &name) {
                     Err(CompilationFail { .. }) => {},
                     Ok(()) | Err(ResultMismatch { .. }) => return Err(CompilationSuccess { name }),
+                    Err(e @ (DriverError { .. } | Crash { .. } | Timeout { .. })) => return Err(e),
                     Err(CompilationSuccess { .. }) => unreachable!("tests may not report CompilationSuccess"),
                 }
             }
             Ok(())
         });
-        TestCase { test, name }
+        let tags = Tags { instruction, inputs: Inputs::InvalidCompile, ..Default::default() };
+        TestCase { test, name, tags, info }
     }
 }
 
@@ -751,8 +1586,212 @@ pub enum TestError {
     },
     /// Used when the test compiled successfully, but found mismatching values
     ResultMismatch {
-        input: String,
-        output: String,
-        expected: String,
+        summary: MismatchSummary,
+    },
+    /// Used when a CUDA driver call fails while running the test
+    DriverError {
+        error: CudaError,
+    },
+    /// Used when the process running the test in isolation exits without reporting a result
+    Crash {
+        status: String,
+        stderr: String,
+    },
+    /// Used when the test runs out of time, either its own or the budget of the whole run
+    Timeout {
+        timeout: Duration,
+        /// Inputs verified in time, unknown when the process running the test was killed
+        coverage: Option<Coverage>,
     },
 }
+
+impl From<CudaError> for TestError {
+    fn from(error: CudaError) -> Self {
+        TestError::DriverError { error }
+    }
+}
+
+impl TestError {
+    /// Name of the variant, as used in reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TestError::CompilationFail { .. } => "CompilationFail",
+            TestError::CompilationSuccess { .. } => "CompilationSuccess",
+            TestError::ResultMismatch { .. } => "ResultMismatch",
+            TestError::DriverError { .. } => "DriverError",
+            TestError::Crash { .. } => "Crash",
+            TestError::Timeout { .. } => "Timeout",
+        }
+    }
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestError::CompilationFail { message } => write!(f, "Compilation failed:\n{message}"),
+            TestError::CompilationSuccess { name } => write!(f, "Compilation mismatch, didn't expect '{name}' to compile"),
+            TestError::DriverError { error } => write!(f, "CUDA driver call failed: {error}"),
+            TestError::Crash { status, stderr } => {
+                write!(f, "Test process crashed, {status}")?;
+                if !stderr.is_empty() {
+                    write!(f, "\n{stderr}")?;
+                }
+                Ok(())
+            }
+            TestError::Timeout { timeout, coverage } => {
+                write!(f, "Timed out after {}s", timeout.as_secs())?;
+                if let Some(Coverage { verified, total }) = coverage {
+                    let percent = *verified as f64 * 100.0 / *total as f64;
+                    write!(f, ", verified {verified} of {total} inputs ({percent:.2}%)")?;
+                }
+                Ok(())
+            }
+            TestError::ResultMismatch { summary } => {
+                write!(f, "{} mismatching values", summary.total)?;
+                let classes: Vec<_> = summary.classes.iter().map(|(class, count)| format!("{class}: {count}")).collect();
+                write!(f, "\n    by input class: {}", classes.join(", "))?;
+                let oracle = if summary.reference { "by reference" } else { "on CPU" };
+                for Mismatch { input, output, expected } in &summary.examples {
+                    write!(f, "\n    with input {input}\n    computed on GPU: {output}\n    computed {oracle}: {expected}")?;
+                }
+                if summary.total > summary.examples.len() {
+                    write!(f, "\n    ... and {} more", summary.total - summary.examples.len())?;
+                }
+                if let Some(path) = &summary.reproducer {
+                    write!(f, "\n    reproducer of the first one written to {}", path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Number of inputs of a test verified before it ran out of time.
+#[derive(Clone, Copy)]
+pub struct Coverage {
+    pub verified: usize,
+    pub total: usize,
+}
+
+/// Limits on how long tests may run, checked between batches of inputs.
+#[derive(Clone, Copy, Default)]
+pub struct TimeLimit {
+    /// Maximum duration of a single test
+    pub timeout: Option<Duration>,
+    /// Point in time at which the whole run has to stop
+    pub end: Option<Instant>,
+}
+
+impl TimeLimit {
+    /// Deadline of a test started at `start`.
+    pub fn deadline(&self, start: Instant) -> Option<Instant> {
+        match (self.timeout.map(|timeout| start + timeout), self.end) {
+            (Some(timeout), Some(end)) => Some(timeout.min(end)),
+            (timeout, end) => timeout.or(end),
+        }
+    }
+}
+
+/// Maximum number of mismatching values kept as examples in a `MismatchSummary`.
+const MAX_MISMATCH_EXAMPLES: usize = 8;
+
+/// Single value for which GPU and CPU results disagree.
+pub struct Mismatch {
+    pub input: String,
+    pub output: String,
+    pub expected: String,
+}
+
+/// Bit patterns of a mismatching value, one buffer per component as passed to the kernel.
+pub struct MismatchBits {
+    pub input: Vec<Vec<u8>>,
+    pub output: Vec<Vec<u8>>,
+    pub expected: Vec<Vec<u8>>,
+}
+
+impl MismatchBits {
+    fn new<I: OnDevice, O: OnDevice>(input: I, output: O, expected: O) -> Self {
+        fn bits<V: OnDevice>(value: V) -> Vec<Vec<u8>> {
+            let mut buffers = vec![Vec::new(); V::COMPONENTS];
+            value.write(&mut buffers);
+            buffers
+        }
+        MismatchBits { input: bits(input), output: bits(output), expected: bits(expected) }
+    }
+}
+
+/// All mismatching values found by a test.
+#[derive(Default)]
+pub struct MismatchSummary {
+    /// Total number of mismatching values
+    pub total: usize,
+    /// First `MAX_MISMATCH_EXAMPLES` mismatching values
+    pub examples: Vec<Mismatch>,
+    /// Number of mismatching values per input class
+    pub classes: BTreeMap<String, usize>,
+    /// Counts of `classes` while the test runs, named once it finishes
+    pub class_keys: BTreeMap<ClassKey, usize>,
+    /// Expected values were produced by the reference CUDA library instead of the host
+    pub reference: bool,
+    /// Bit patterns of the first mismatching value, for writing a reproducer
+    pub first: Option<Box<MismatchBits>>,
+    /// Directory of the reproducer written for the first mismatching value, see `repro::write`
+    pub reproducer: Option<PathBuf>,
+}
+
+impl MismatchSummary {
+    fn record<I: OnDevice + DebugRich + InputClass, O: OnDevice + DebugRich>(&mut self, input: I, output: O, expected: O) {
+        if self.first.is_none() {
+            self.first = Some(Box::new(MismatchBits::new(input, output, expected)));
+        }
+        self.total += 1;
+        *self.class_keys.entry(input.input_class()).or_default() += 1;
+        if self.examples.len() < MAX_MISMATCH_EXAMPLES {
+            self.examples.push(Mismatch {
+                input: input.debug_rich(),
+                output: output.debug_rich(),
+                expected: expected.debug_rich(),
+            });
+        }
+    }
+
+    fn merge(&mut self, other: MismatchSummary) {
+        self.total += other.total;
+        for (class, count) in other.classes {
+            *self.classes.entry(class).or_default() += count;
+        }
+        for (key, count) in other.class_keys {
+            *self.class_keys.entry(key).or_default() += count;
+        }
+        let room = MAX_MISMATCH_EXAMPLES - self.examples.len();
+        self.examples.extend(other.examples.into_iter().take(room));
+        self.first = self.first.take().or(other.first);
+    }
+
+    fn name_classes(&mut self) {
+        for (key, count) in mem::take(&mut self.class_keys) {
+            *self.classes.entry(key.to_string()).or_default() += count;
+        }
+    }
+
+    fn into_result(mut self) -> Result<(), TestError> {
+        self.name_classes();
+        if self.total == 0 {
+            Ok(())
+        } else {
+            Err(TestError::ResultMismatch { summary: self })
+        }
+    }
+
+    /// Like `into_result`, for a test started at `started` that ran out of time after verifying
+    /// `verified` of its `total` inputs. Mismatches found until then take precedence.
+    fn into_result_partial(mut self, started: Instant, verified: usize, total: usize) -> Result<(), TestError> {
+        self.name_classes();
+        if self.total == 0 {
+            let coverage = Some(Coverage { verified, total });
+            Err(TestError::Timeout { timeout: started.elapsed(), coverage })
+        } else {
+            Err(TestError::ResultMismatch { summary: self })
+        }
+    }
+}
@@ -0,0 +1,716 @@
+use crate::common::{self, saturate, Rounding};
+use crate::test::{make_random, make_range, DebugRich, EdgeCases, InputClass, Inputs, OnDevice, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
+use half::{bf16, f16};
+use num::Float;
+use rand::Rng;
+use std::{array, marker::PhantomData, mem};
+
+pub static PTX: &str = include_str!("arith.ptx");
+pub static FMA_PTX: &str = include_str!("fma.ptx");
+
+pub fn all_tests() -> Vec<TestCase> {
+    let mut tests = vec![];
+    for op in [Op::Add, Op::Sub, Op::Mul] {
+        tests.push(arith_coarse(op));
+        for rnd in roundings() {
+            for ftz in [false, true] {
+                for sat in [false, true] {
+                    tests.push(arith_rng::<f32>(op, rnd, ftz, sat));
+                }
+            }
+            // .ftz and .sat only exist for .f32
+            tests.push(arith_rng::<f64>(op, rnd, false, false));
+        }
+        // Half precision only rounds to nearest
+        for ftz in [false, true] {
+            for sat in [false, true] {
+                tests.push(arith_range_f16(op, ftz, sat));
+                tests.push(arith_rng::<[f16; 2]>(op, Rounding::Rn, ftz, sat));
+            }
+        }
+    }
+    for rnd in roundings() {
+        for ftz in [false, true] {
+            for sat in [false, true] {
+                tests.push(fma_rng::<f32>(rnd, ftz, sat, false));
+            }
+        }
+        tests.push(fma_rng::<f64>(rnd, false, false, false));
+    }
+    // Host fma on Windows would round results of half precision twice
+    if cfg!(not(windows)) {
+        for ftz in [false, true] {
+            for (sat, relu) in [(false, false), (true, false), (false, true)] {
+                tests.push(fma_rng::<f16>(Rounding::Rn, ftz, sat, relu));
+                tests.push(fma_rng::<[f16; 2]>(Rounding::Rn, ftz, sat, relu));
+            }
+        }
+        // bf16 has neither .ftz nor .sat
+        for relu in [false, true] {
+            tests.push(fma_rng::<bf16>(Rounding::Rn, false, false, relu));
+            tests.push(fma_rng::<[bf16; 2]>(Rounding::Rn, false, false, relu));
+        }
+    }
+    tests
+}
+
+/// Rounding modes with an exact host reference. Windows has no MPFR and relies on host
+/// arithmetic, which only rounds to nearest reliably.
+fn roundings() -> Vec<Rounding> {
+    if cfg!(not(windows)) {
+        vec![Rounding::Rn, Rounding::Rz, Rounding::Rm, Rounding::Rp]
+    } else {
+        vec![Rounding::Rn]
+    }
+}
+
+/// Exhaustive test on operands with only 7 explicit mantissa bits, which still covers every
+/// combination of signs, exponents and special values of both operands.
+fn arith_coarse(op: Op) -> TestCase {
+    let arith = Arith::<f32>::new(op, Rounding::Rn, false, false);
+    let tags = arith.tags(Inputs::Exhaustive);
+    TestCase::new(format!("{}_coarse", arith.name()), tags, make_range(arith))
+}
+
+fn arith_range_f16(op: Op, ftz: bool, sat: bool) -> TestCase {
+    let arith = Arith::<f16>::new(op, Rounding::Rn, ftz, sat);
+    let tags = arith.tags(Inputs::Exhaustive);
+    TestCase::new(arith.name(), tags, make_range(arith))
+}
+
+fn arith_rng<V: Value>(op: Op, rnd: Rounding, ftz: bool, sat: bool) -> TestCase {
+    let arith = Arith::<V>::new(op, rnd, ftz, sat);
+    let tags = arith.tags(Inputs::Random);
+    TestCase::new(arith.name(), tags, make_random(arith))
+}
+
+fn fma_rng<V: Value>(rnd: Rounding, ftz: bool, sat: bool, relu: bool) -> TestCase {
+    let fma = Fma::<V> { rnd, ftz, sat, relu, _phantom: PhantomData };
+    let tags = Tags {
+        instruction: "fma",
+        types: vec![V::name()],
+        rounding: Some(rnd.as_str()),
+        ftz,
+        sat,
+        modifiers: if relu { vec!["relu"] } else { Vec::new() },
+        inputs: Inputs::Random,
+    };
+    let ftz = if ftz { "_ftz" } else { "" };
+    let sat = if sat { "_sat" } else { "" };
+    let relu = if relu { "_relu" } else { "" };
+    TestCase::new(format!("fma_{}{}{}{}_{}", rnd.as_str(), ftz, sat, relu, V::name()), tags, make_random(fma))
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Sub => "sub",
+            Op::Mul => "mul",
+        }
+    }
+}
+
+/// Float types of `add`, `sub`, `mul`, `fma`, `min` and `max`, assembled from raw bits when
+/// generating inputs.
+pub trait ArithFloat: PtxScalar + Float {
+    /// Explicitly stored bits of the significand
+    const MANTISSA_BITS: u32;
+    const EXPONENT_BITS: u32;
+
+    fn from_bits(bits: u64) -> Self;
+    fn bits(self) -> u64;
+    fn flush_to_zero(&mut self, ftz: bool);
+}
+
+impl ArithFloat for f16 {
+    const MANTISSA_BITS: u32 = 10;
+    const EXPONENT_BITS: u32 = 5;
+
+    fn from_bits(bits: u64) -> Self {
+        f16::from_bits(bits as u16)
+    }
+
+    fn bits(self) -> u64 {
+        self.to_bits() as u64
+    }
+
+    fn flush_to_zero(&mut self, ftz: bool) {
+        common::flush_to_zero_f16(self, ftz);
+    }
+}
+
+impl ArithFloat for bf16 {
+    const MANTISSA_BITS: u32 = 7;
+    const EXPONENT_BITS: u32 = 8;
+
+    fn from_bits(bits: u64) -> Self {
+        bf16::from_bits(bits as u16)
+    }
+
+    fn bits(self) -> u64 {
+        self.to_bits() as u64
+    }
+
+    // There is no .ftz for .bf16
+    fn flush_to_zero(&mut self, _ftz: bool) {}
+}
+
+impl ArithFloat for f32 {
+    const MANTISSA_BITS: u32 = 23;
+    const EXPONENT_BITS: u32 = 8;
+
+    fn from_bits(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+
+    fn bits(self) -> u64 {
+        self.to_bits() as u64
+    }
+
+    fn flush_to_zero(&mut self, ftz: bool) {
+        common::flush_to_zero_f32(self, ftz);
+    }
+}
+
+impl ArithFloat for f64 {
+    const MANTISSA_BITS: u32 = 52;
+    const EXPONENT_BITS: u32 = 11;
+
+    fn from_bits(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+
+    fn bits(self) -> u64 {
+        self.to_bits()
+    }
+
+    // There is no .ftz for .f64
+    fn flush_to_zero(&mut self, _ftz: bool) {}
+}
+
+/// Value of a register: a single float or two of them packed like `.f16x2`. Every lane is
+/// computed and checked independently of the other one.
+pub trait Value: OnDevice + DebugRich + InputClass + EdgeCases + 'static {
+    type Lane: ArithFloat;
+    const LANES: usize;
+
+    fn name() -> String;
+    fn lane(self, index: usize) -> Self::Lane;
+    fn from_lanes(lane: impl FnMut(usize) -> Self::Lane) -> Self;
+
+    /// Same value in every lane, any NaN matches any other NaN.
+    fn matches(self, other: Self) -> bool {
+        (0..Self::LANES).all(|i| {
+            let (a, b) = (self.lane(i), other.lane(i));
+            (a.is_nan() && b.is_nan()) || a.bits() == b.bits()
+        })
+    }
+}
+
+impl<T: ArithFloat> Value for T {
+    type Lane = T;
+    const LANES: usize = 1;
+
+    fn name() -> String {
+        <T as PtxScalar>::name().to_string()
+    }
+
+    fn lane(self, _index: usize) -> T {
+        self
+    }
+
+    fn from_lanes(mut lane: impl FnMut(usize) -> T) -> Self {
+        lane(0)
+    }
+}
+
+impl<T: ArithFloat> Value for [T; 2] {
+    type Lane = T;
+    const LANES: usize = 2;
+
+    fn name() -> String {
+        format!("{}x2", <T as PtxScalar>::name())
+    }
+
+    fn lane(self, index: usize) -> T {
+        self[index]
+    }
+
+    fn from_lanes(lane: impl FnMut(usize) -> T) -> Self {
+        array::from_fn(lane)
+    }
+}
+
+/// PTX body of an instruction writing `result` of `TYPE` from loaded `value_a`, `value_b`, ...
+fn body<V: Value>(template: &str, op: &str) -> String {
+    template
+        .replace("<OP>", op)
+        .replace("<TYPE_SIZE>", &V::size_of().to_string())
+        .replace("<TYPE>", &V::name())
+        .replace("<BTYPE>", &format!("b{}", V::size_of() * 8))
+}
+
+/// `add`, `sub` or `mul` of two operands, checked bit for bit against the exact result rounded
+/// once to the destination type.
+struct Arith<V> {
+    op: Op,
+    rnd: Rounding,
+    ftz: bool,
+    sat: bool,
+    _phantom: PhantomData<V>,
+}
+
+impl<V: Value> Arith<V> {
+    fn new(op: Op, rnd: Rounding, ftz: bool, sat: bool) -> Self {
+        Arith { op, rnd, ftz, sat, _phantom: PhantomData }
+    }
+
+    fn name(&self) -> String {
+        let ftz = if self.ftz { "_ftz" } else { "" };
+        let sat = if self.sat { "_sat" } else { "" };
+        format!("{}_{}{}{}_{}", self.op.as_str(), self.rnd.as_str(), ftz, sat, V::name())
+    }
+
+    fn tags(&self, inputs: Inputs) -> Tags {
+        Tags {
+            instruction: self.op.as_str(),
+            types: vec![V::name()],
+            rounding: Some(self.rnd.as_str()),
+            ftz: self.ftz,
+            sat: self.sat,
+            inputs,
+            ..Default::default()
+        }
+    }
+}
+
+impl<V: Value> TestPtx for Arith<V> {
+    fn body(&self) -> String {
+        let ftz = if self.ftz { ".ftz" } else { "" };
+        let sat = if self.sat { ".sat" } else { "" };
+        body::<V>(PTX, &format!("{}{}{}{}.{}", self.op.as_str(), self.rnd.as_ptx(), ftz, sat, V::name()))
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "output",
+        ]
+    }
+}
+
+impl<V: Value> TestCommon for Arith<V> {
+    type Input = (V, V);
+    type Output = V;
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let expected = V::from_lanes(|i| {
+            let (mut a, mut b) = (input.0.lane(i), input.1.lane(i));
+            a.flush_to_zero(self.ftz);
+            b.flush_to_zero(self.ftz);
+            // Subnormal results are flushed after rounding
+            let mut result = os::arith(self.op, a, b, self.rnd);
+            result.flush_to_zero(self.ftz);
+            if self.sat {
+                result = saturate(result);
+            }
+            result
+        });
+        if expected.matches(output) {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl RangeTest for Arith<f32> {
+    fn generate(&self, input: u32) -> Self::Input {
+        (f32::from_bits(input << 16), f32::from_bits(input & 0xffff_0000))
+    }
+}
+
+impl RangeTest for Arith<f16> {
+    fn generate(&self, input: u32) -> Self::Input {
+        common::halves(input)
+    }
+}
+
+impl<V: Value> RandomTest for Arith<V> {
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        // Packed values have two lanes at most, a fixed array avoids allocating for every input
+        let zero = <V::Lane as num::Zero>::zero();
+        let mut lanes = [(zero, zero); 2];
+        for lane in &mut lanes[..V::LANES] {
+            *lane = random_operands(rng);
+        }
+        (V::from_lanes(|i| lanes[i].0), V::from_lanes(|i| lanes[i].1))
+    }
+}
+
+/// `fma` of three operands, checked bit for bit against the exact `a * b + c` rounded once. An
+/// implementation rounding the product first, or computing in a wider type and rounding the
+/// result again, fails on the targeted inputs.
+struct Fma<V> {
+    rnd: Rounding,
+    ftz: bool,
+    sat: bool,
+    /// Negative results become +0.0, only for half precision
+    relu: bool,
+    _phantom: PhantomData<V>,
+}
+
+impl<V: Value> TestPtx for Fma<V> {
+    fn body(&self) -> String {
+        let ftz = if self.ftz { ".ftz" } else { "" };
+        let sat = if self.sat { ".sat" } else { "" };
+        let relu = if self.relu { ".relu" } else { "" };
+        body::<V>(FMA_PTX, &format!("fma{}{}{}{}.{}", self.rnd.as_ptx(), ftz, sat, relu, V::name()))
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "input_c",
+            "output",
+        ]
+    }
+}
+
+impl<V: Value> TestCommon for Fma<V> {
+    type Input = (V, V, V);
+    type Output = V;
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let expected = V::from_lanes(|i| {
+            let (mut a, mut b, mut c) = (input.0.lane(i), input.1.lane(i), input.2.lane(i));
+            a.flush_to_zero(self.ftz);
+            b.flush_to_zero(self.ftz);
+            c.flush_to_zero(self.ftz);
+            let mut result = os::fma(a, b, c, self.rnd);
+            result.flush_to_zero(self.ftz);
+            if self.sat {
+                result = saturate(result);
+            }
+            if self.relu && result.is_sign_negative() && !result.is_nan() {
+                result = <V::Lane as num::Zero>::zero();
+            }
+            result
+        });
+        if expected.matches(output) {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl<V: Value> RandomTest for Fma<V> {
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        let zero = <V::Lane as num::Zero>::zero();
+        let mut lanes = [(zero, zero, zero); 2];
+        for lane in &mut lanes[..V::LANES] {
+            *lane = random_fma_operands(rng);
+        }
+        (V::from_lanes(|i| lanes[i].0), V::from_lanes(|i| lanes[i].1), V::from_lanes(|i| lanes[i].2))
+    }
+}
+
+fn mantissa_mask<T: ArithFloat>() -> u64 {
+    (1 << T::MANTISSA_BITS) - 1
+}
+
+/// Biased exponent of infinities and NaNs.
+fn max_exponent<T: ArithFloat>() -> u64 {
+    (1 << T::EXPONENT_BITS) - 1
+}
+
+fn bias<T: ArithFloat>() -> u64 {
+    max_exponent::<T>() / 2
+}
+
+fn compose<T: ArithFloat>(negative: bool, exponent: u64, mantissa: u64) -> T {
+    let sign = (negative as u64) << (T::EXPONENT_BITS + T::MANTISSA_BITS);
+    T::from_bits(sign | exponent << T::MANTISSA_BITS | mantissa & mantissa_mask::<T>())
+}
+
+/// Normal float with the significand `significand` of `width` bits, leading one included.
+fn from_significand<T: ArithFloat>(exponent: u64, significand: u64, width: u32) -> T {
+    compose(false, exponent, significand << (T::MANTISSA_BITS + 1 - width))
+}
+
+fn random_mantissa<T: ArithFloat, R: Rng>(rng: &mut R) -> u64 {
+    rng.gen::<u64>() & mantissa_mask::<T>()
+}
+
+/// Operand from either end of the exponent range, with an all zeros, all ones or random mantissa.
+fn random_extreme<T: ArithFloat, R: Rng>(rng: &mut R) -> T {
+    let max = max_exponent::<T>();
+    let exponent = [0, 1, 2, max - 2, max - 1][rng.gen_range(0..5)];
+    let mantissa = match rng.gen_range(0..3) {
+        0 => 0,
+        1 => mantissa_mask::<T>(),
+        _ => random_mantissa::<T, R>(rng),
+    };
+    compose(rng.gen(), exponent, mantissa)
+}
+
+/// Odd significand of `width` bits, leading one included.
+fn random_odd_significand<R: Rng>(rng: &mut R, width: u32) -> u64 {
+    rng.gen_range(1 << (width - 1)..1 << width) | 1
+}
+
+/// Positive operands with odd significands, whose product is one bit wider than the destination
+/// when its top bit is set, which makes it a tie of the rounding.
+fn halfway_product<T: ArithFloat, R: Rng>(rng: &mut R) -> (T, T) {
+    let bias = bias::<T>();
+    let width_a = (T::MANTISSA_BITS + 3) / 2;
+    let width_b = T::MANTISSA_BITS + 2 - width_a;
+    let exponent_a = bias + rng.gen_range(0..bias) - bias / 2;
+    let exponent_b = bias + rng.gen_range(0..bias) - bias / 2;
+    let a = from_significand(exponent_a, random_odd_significand(rng, width_a), width_a);
+    (a, from_significand(exponent_b, random_odd_significand(rng, width_b), width_b))
+}
+
+/// Biased exponent of `a * b`, which is either this or one more.
+fn product_exponent<T: ArithFloat>(a: T, b: T) -> u64 {
+    let exponent = |x: T| (x.bits() >> T::MANTISSA_BITS) & max_exponent::<T>();
+    (exponent(a) + exponent(b)).saturating_sub(bias::<T>())
+}
+
+/// Random operands, mostly from classes of inputs where rounding is hardest to get right:
+/// subnormals, both ends of the exponent range and exact results halfway between two floats.
+/// Every class is used for all operations, signs and order of operands are random.
+fn random_operands<T: ArithFloat, R: Rng>(rng: &mut R) -> (T, T) {
+    let mantissa_bits = T::MANTISSA_BITS as u64;
+    let max = max_exponent::<T>();
+    let bias = bias::<T>();
+    let (mut a, mut b): (T, T) = match rng.gen_range(0..10) {
+        // Any bit patterns, including infinities and NaNs
+        0 | 1 => (T::from_bits(rng.gen()), T::from_bits(rng.gen())),
+        // Subnormals, against other subnormals or small normals
+        2 => {
+            let a = compose(false, 0, random_mantissa::<T, R>(rng));
+            let b = compose(false, rng.gen_range(0..mantissa_bits + 3), random_mantissa::<T, R>(rng));
+            (a, b)
+        }
+        3 => (random_extreme(rng), random_extreme(rng)),
+        // Products close to overflow or deep into subnormals
+        4 => {
+            let target = if rng.gen() { rng.gen_range(0..3) } else { max - rng.gen_range(1..4) };
+            let exponent_a = rng.gen_range(1..max);
+            let exponent_b = (target + bias).saturating_sub(exponent_a).clamp(1, max - 1);
+            let a = compose(false, exponent_a, random_mantissa::<T, R>(rng));
+            (a, compose(false, exponent_b, random_mantissa::<T, R>(rng)))
+        }
+        // Sums with the second operand at or close to half an ulp of the first one, exact halves
+        // are ties of the rounding
+        5 | 6 => {
+            let exponent_a = rng.gen_range(mantissa_bits + 4..max);
+            let exponent_b = exponent_a - mantissa_bits - 1 - rng.gen_range(0..3);
+            let mantissa_b = if rng.gen() { 0 } else { random_mantissa::<T, R>(rng) };
+            let a = compose(false, exponent_a, random_mantissa::<T, R>(rng));
+            (a, compose(false, exponent_b, mantissa_b))
+        }
+        7 | 8 => halfway_product(rng),
+        // Operands a few ulps apart, their difference is exact and possibly zero or subnormal
+        _ => {
+            let a = compose::<T>(false, rng.gen_range(1..max - 1), random_mantissa::<T, R>(rng));
+            let b = T::from_bits((a.bits() + rng.gen_range(0..8)).saturating_sub(4));
+            (a, b)
+        }
+    };
+    if rng.gen() {
+        b = -b;
+    }
+    if rng.gen() {
+        a = -a;
+        b = -b;
+    }
+    if rng.gen() {
+        mem::swap(&mut a, &mut b);
+    }
+    (a, b)
+}
+
+/// Random operands of `fma`, mostly with addends that make a difference between rounding once and
+/// rounding twice, either after the multiplication or after computing in a wider type.
+fn random_fma_operands<T: ArithFloat, R: Rng>(rng: &mut R) -> (T, T, T) {
+    let mantissa_bits = T::MANTISSA_BITS as u64;
+    match rng.gen_range(0..6) {
+        // Operands of add, sub and mul tests with any third one
+        0 | 1 => {
+            let (a, b) = random_operands(rng);
+            (a, b, random_operands(rng).0)
+        }
+        // Addend cancelling the rounded product, so the result is the rounding error of the
+        // product, which is lost entirely by a separate multiplication
+        2 => {
+            let (a, b) = random_operands(rng);
+            (a, b, -(a * b))
+        }
+        // Product halfway between two floats and an addend far below its last bit, which only
+        // breaks the tie when rounded once. Even rounding of the result in double the precision
+        // loses the addend.
+        3 | 4 => {
+            let (a, b) = halfway_product::<T, R>(rng);
+            let shift = rng.gen_range(mantissa_bits + 3..3 * mantissa_bits);
+            let c = compose(rng.gen(), product_exponent(a, b).saturating_sub(shift), random_mantissa::<T, R>(rng));
+            (with_random_sign(rng, a), with_random_sign(rng, b), c)
+        }
+        // Addend at or close to half an ulp of a random product
+        _ => {
+            let (a, b) = random_operands(rng);
+            let exponent = product_exponent(a, b).saturating_sub(mantissa_bits + 1 + rng.gen_range(0..2));
+            let mantissa = if rng.gen() { 0 } else { random_mantissa::<T, R>(rng) };
+            (a, b, compose(rng.gen(), exponent, mantissa))
+        }
+    }
+}
+
+fn with_random_sign<T: ArithFloat, R: Rng>(rng: &mut R, value: T) -> T {
+    if rng.gen() {
+        -value
+    } else {
+        value
+    }
+}
+
+#[cfg(not(windows))]
+mod os {
+    use super::{bias, ArithFloat, Op};
+    use crate::common::Rounding;
+    use num::NumCast;
+    use rug::{float::Round, Float};
+    use std::cmp::Ordering;
+
+    fn rug_round(rnd: Rounding) -> Round {
+        match rnd {
+            Rounding::Rzi | Rounding::Rz => Round::Zero,
+            Rounding::Default | Rounding::Rni | Rounding::Rn => Round::Nearest,
+            Rounding::Rpi | Rounding::Rp => Round::Up,
+            Rounding::Rmi | Rounding::Rm => Round::Down,
+        }
+    }
+
+    /// Whether a rounding of a value with the sign `negative` goes away from zero, `nearest` tells
+    /// if the value is beyond the midpoint for rounding to nearest.
+    fn away_from_zero(round: Round, negative: bool, nearest: bool) -> bool {
+        match round {
+            Round::Nearest => nearest,
+            Round::Up => !negative,
+            Round::Down => negative,
+            _ => false,
+        }
+    }
+
+    fn with_sign<T: ArithFloat>(negative: bool, magnitude: T) -> T {
+        if negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Rounding of a non-zero value below the smallest subnormal, which is outside of the range
+    /// handled by `subnormalize_ieee_round`. `result` is the value rounded to full precision in
+    /// the direction given by `ordering`.
+    fn below_subnormals<T: ArithFloat>(result: &Float, ordering: Ordering, round: Round) -> Option<T> {
+        let min = T::from_bits(1);
+        if !result.is_normal() || *result.as_abs() >= min.to_f64().unwrap() {
+            return None;
+        }
+        let negative = result.is_sign_negative();
+        // Exact magnitude compared with half of the smallest subnormal, also for ties at full
+        // precision
+        let magnitude_ordering = if negative { ordering } else { ordering.reverse() };
+        let half = Float::with_val(1, min.to_f64().unwrap()) >> 1u32;
+        let beyond_half = result.as_abs().partial_cmp(&half).unwrap().then(magnitude_ordering) == Ordering::Greater;
+        let magnitude = if away_from_zero(round, negative, beyond_half) { min } else { <T as num::Zero>::zero() };
+        Some(with_sign(negative, magnitude))
+    }
+
+    /// Operand as an MPFR value, the conversions through f64 are exact.
+    fn exact<T: ArithFloat>(value: T) -> Float {
+        Float::with_val(T::MANTISSA_BITS + 1, value.to_f64().unwrap())
+    }
+
+    pub fn arith<T: ArithFloat>(op: Op, a: T, b: T, rnd: Rounding) -> T {
+        let round = rug_round(rnd);
+        let precision = T::MANTISSA_BITS + 1;
+        let (a, b) = (exact(a), exact(b));
+        let (result, ordering) = match op {
+            Op::Add => Float::with_val_round(precision, &a + &b, round),
+            Op::Sub => Float::with_val_round(precision, &a - &b, round),
+            Op::Mul => Float::with_val_round(precision, &a * &b, round),
+        };
+        to_float(result, ordering, round)
+    }
+
+    pub fn fma<T: ArithFloat>(a: T, b: T, c: T, rnd: Rounding) -> T {
+        let round = rug_round(rnd);
+        let (a, b, c) = (exact(a), exact(b), exact(c));
+        let (result, ordering) = Float::with_val_round(T::MANTISSA_BITS + 1, &a * &b + &c, round);
+        to_float(result, ordering, round)
+    }
+
+    /// Round `result`, the exact result already rounded to the precision of `T` in the direction
+    /// given by `ordering`, into the exponent range of `T`.
+    fn to_float<T: ArithFloat>(mut result: Float, ordering: Ordering, round: Round) -> T {
+        if let Some(result) = below_subnormals(&result, ordering, round) {
+            return result;
+        }
+        // MPFR has no subnormals, rounding again at their reduced precision accounts for the
+        // direction of the first rounding, so it is still a single rounding of the exact result.
+        // The smallest normal is 0.5 * 2^normal_exp_min in MPFR, which also covers bf16 unlike
+        // `subnormalize_ieee_round`, which only knows precisions of IEEE formats.
+        let normal_exp_min = 2 - bias::<T>() as i32;
+        result.subnormalize_round(normal_exp_min, ordering, round);
+        let max = <T as num::Float>::max_value().to_f64().unwrap();
+        if result.is_finite() && *result.as_abs() > max {
+            let negative = result.is_sign_negative();
+            let magnitude = if away_from_zero(round, negative, true) { T::infinity() } else { <T as num::Float>::max_value() };
+            return with_sign(negative, magnitude);
+        }
+        <T as NumCast>::from(result.to_f64()).unwrap()
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use super::{ArithFloat, Op};
+    use crate::common::Rounding;
+
+    fn apply<F: num::Float>(op: Op, a: F, b: F) -> F {
+        match op {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+        }
+    }
+
+    // Only .rn is tested on Windows, which is the rounding of host arithmetic. Results of half
+    // precision are exact in f64 and rounded once when converted back.
+    pub fn arith<T: ArithFloat>(op: Op, a: T, b: T, _rnd: Rounding) -> T {
+        if T::size_of() == 2 {
+            let value = apply(op, a.to_f64().unwrap(), b.to_f64().unwrap());
+            <T as num::NumCast>::from(value).unwrap()
+        } else {
+            apply(op, a, b)
+        }
+    }
+
+    pub fn fma<T: ArithFloat>(a: T, b: T, c: T, _rnd: Rounding) -> T {
+        a.mul_add(b, c)
+    }
+}
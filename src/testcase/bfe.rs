@@ -1,4 +1,4 @@
-use crate::test::{make_random, PtxScalar, RandomTest, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_random, Inputs, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use num::cast::AsPrimitive;
 use num::PrimInt;
 use num::{traits::FromBytes, Zero};
@@ -21,8 +21,9 @@ fn bfe_rng<T: PtxScalar + AsPrimitive<usize> + PrimInt + Default>() -> TestCase
 where
     Standard: Distribution<T>,
 {
-    let test = make_random::<Bfe<T>>();
-    TestCase::new(format!("bfe_rng_{}", T::name()), test)
+    let test = make_random(Bfe::<T>::default());
+    let tags = Tags { instruction: "bfe", types: vec![T::name().to_string()], inputs: Inputs::Random, ..Default::default() };
+    TestCase::new(format!("bfe_rng_{}", T::name()), tags, test)
 }
 
 #[derive(Default)]
@@ -1,4 +1,4 @@
-use crate::test::{make_random, PtxScalar, RandomTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_random, Inputs, PtxScalar, RandomTest, Tags, TestCase, TestCommon, TestPtx};
 use num::{cast::AsPrimitive, PrimInt};
 use rand::{distributions::Standard, prelude::Distribution, Rng};
 use std::mem;
@@ -17,8 +17,9 @@ where
     Standard: Distribution<T>,
 {
     let bits = mem::size_of::<T>() * 8;
-    let test = make_random::<Bfi<T>>();
-    TestCase::new(format!("bfi_rng_b{}", bits), test)
+    let test = make_random(Bfi::<T>::default());
+    let tags = Tags { instruction: "bfi", types: vec![format!("b{}", bits)], inputs: Inputs::Random, ..Default::default() };
+    TestCase::new(format!("bfi_rng_b{}", bits), tags, test)
 }
 
 #[derive(Default)]
@@ -1,4 +1,4 @@
-use crate::test::{self, make_range, PtxScalar, TestCase, TestCommon, TestPtx};
+use crate::test::{self, make_range, PtxScalar, Tags, TestCase, TestCommon, TestPtx};
 use num::PrimInt;
 use rand::{distributions::Standard, prelude::Distribution};
 use std::mem;
@@ -17,7 +17,8 @@ where
 {
     let bits = mem::size_of::<u32>() * 8;
     let test = make_range(Brev::<u32>::new());
-    TestCase::new(format!("brev_b{}", bits), test)
+    let tags = Tags { instruction: "brev", types: vec![format!("b{}", bits)], ..Default::default() };
+    TestCase::new(format!("brev_b{}", bits), tags, test)
 }
 
 pub struct Brev<T: PtxScalar> {
@@ -1,5 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use core::f32;
 use std::mem;
 
@@ -15,8 +15,9 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn cos(ftz: bool) -> TestCase {
     let test = make_range(Cos { ftz });
+    let tags = Tags { instruction: "cos", types: vec!["f32".to_string()], rounding: Some("approx"), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("cos_approx{}", ftz), test)
+    TestCase::new(format!("cos_approx{}", ftz), tags, test)
 }
 
 pub struct Cos {
@@ -1,9 +1,10 @@
 use crate::common::{llvm_get_rounding, llvm_set_rounding};
-use crate::test::{make_range, TestFunction, TestPtx};
+use crate::test::{make_range, Tags, Test, TestPtx};
 use crate::{
     common::Rounding,
     test::{self, PtxScalar, TestCase, TestCommon},
 };
+use half::bf16;
 use num::traits::AsPrimitive;
 use num::traits::ConstOne;
 use num::traits::ConstZero;
@@ -114,11 +115,11 @@ macro_rules! gen_test {
     ($vec:expr, $invalid:expr, $rnd:expr, $ftz:expr, $sat:expr, $input:ty, [$($output:ty),*]) => {
         $(
             {
-                let (name, test) = test_case::<$output, $input>($rnd, $ftz, $sat);
+                let (name, tags, test) = test_case::<$output, $input>($rnd, $ftz, $sat, None);
                 if is_invalid_cvt::<$output, $input>($rnd.as_ptx(), $ftz, $sat) {
                     $invalid.push((name, test));
                 } else {
-                    $vec.push(test::TestCase::new(name, test));
+                    $vec.push(test::TestCase::new(name, tags, test));
                 }
             }
         )*
@@ -129,15 +130,18 @@ struct Cvt<To: PtxScalar, From: PtxScalar> {
     rnd: Rounding,
     ftz: bool,
     sat: bool,
+    /// Input clamping of `.relu` on the host, see `HostRelu`
+    relu: Option<fn(From) -> From>,
     _phantom: std::marker::PhantomData<(To, From)>,
 }
 
 impl<To: PtxScalar, From: PtxScalar> Cvt<To, From> {
-    fn new(rnd: Rounding, ftz: bool, sat: bool) -> Self {
+    fn new(rnd: Rounding, ftz: bool, sat: bool, relu: Option<fn(From) -> From>) -> Self {
         Self {
             rnd,
             ftz,
             sat,
+            relu,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -148,11 +152,16 @@ impl<To: PtxScalar, From: PtxScalar> TestPtx for Cvt<To, From> {
         let src = include_str!("cvt.ptx");
         let ftz = if self.ftz { ".ftz" } else { "" };
         let sat = if self.sat { ".sat" } else { "" };
+        let relu = if self.relu.is_some() { ".relu" } else { "" };
         let rnd = self.rnd.as_ptx();
-        let modifiers = format!("{}{}{}", rnd, ftz, sat);
+        let modifiers = format!("{}{}{}{}", rnd, relu, ftz, sat);
         let input_bits = mem::size_of::<From>() * 8;
         let output_bits = mem::size_of::<To>() * 8;
+        let input_reg = if From::untyped_registers() { format!("b{input_bits}") } else { From::name().to_string() };
+        let output_reg = if To::untyped_registers() { format!("b{output_bits}") } else { To::name().to_string() };
         src
+            .replace("<INPUT_REG>", &input_reg)
+            .replace("<OUTPUT_REG>", &output_reg)
             .replace("<INPUT>", From::name())
             // PTX disallows ld.half::f16, but allows ld.b16 and implictly converts to half::f16
             .replace("<INPUT_LD>", &format!("b{input_bits}"))
@@ -177,6 +186,7 @@ impl<To: PtxScalar, From: PtxScalar + HostConvert<To>> TestCommon for Cvt<To, Fr
     type Output = To;
 
     fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let input = self.relu.map_or(input, |relu| relu(input));
         <Self::Input as HostConvert<Self::Output>>::convert(
             input, self.rnd, self.ftz, self.sat, output,
         )
@@ -201,9 +211,11 @@ fn test_case<To: PtxScalar, From: PtxScalar + HostConvert<To>>(
     rnd: Rounding,
     ftz: bool,
     sat: bool,
+    relu: Option<fn(From) -> From>,
 ) -> (
     String,
-    TestFunction,
+    Tags,
+    Test,
 ) {
     let rnd_txt = match rnd {
         Rounding::Default => "",
@@ -218,21 +230,39 @@ fn test_case<To: PtxScalar, From: PtxScalar + HostConvert<To>>(
     };
     let ftz_txt = if ftz { "_ftz" } else { "" };
     let sat_txt = if sat { "_sat" } else { "" };
+    let relu_txt = if relu.is_some() { "_relu" } else { "" };
     let name = format!(
-        "cvt{rnd_txt}{ftz_txt}{sat_txt}_{}_{}",
+        "cvt{rnd_txt}{relu_txt}{ftz_txt}{sat_txt}_{}_{}",
         To::name(),
         From::name()
     );
-    let test = make_range(Cvt::<To, From>::new(rnd, ftz, sat));
-    (name, test)
+    let tags = Tags {
+        instruction: "cvt",
+        types: vec![To::name().to_string(), From::name().to_string()],
+        rounding: Some(rnd.as_str()).filter(|rnd| !rnd.is_empty()),
+        ftz,
+        sat,
+        modifiers: if relu.is_some() { vec!["relu"] } else { Vec::new() },
+        ..Default::default()
+    };
+    let test = make_range(Cvt::<To, From>::new(rnd, ftz, sat, relu));
+    (name, tags, test)
 }
 
 pub fn all_tests() -> Vec<TestCase> {
     let mut result = Vec::new();
     let mut invalid_tests = Vec::new();
     gen_test!(result, invalid_tests);
+    // bf16 only converts from f32 rounding to nearest and back to f32, which doesn't fit the matrix
+    for relu in [None, Some(<f32 as HostRelu<bf16>>::relu as fn(f32) -> f32)] {
+        let (name, tags, test) = test_case::<bf16, f32>(Rounding::Rn, false, false, relu);
+        result.push(test::TestCase::new(name, tags, test));
+    }
+    let (name, tags, test) = test_case::<f32, bf16>(Rounding::Default, false, false, None);
+    result.push(test::TestCase::new(name, tags, test));
     result.push(TestCase::join_invalid_tests(
         "cvt_invalid".to_string(),
+        "cvt",
         invalid_tests,
     ));
     result
@@ -242,6 +272,13 @@ trait HostConvert<To: PtxScalar>: Copy {
     fn convert(self, rnd: Rounding, ftz: bool, sat: bool, expected: To) -> Result<(), To>;
 }
 
+/// Conversions supporting `.relu`, where clamping the input gives the same result as clamping
+/// the output.
+trait HostRelu<To: PtxScalar>: HostConvert<To> {
+    /// Input clamped by `.relu`
+    fn relu(self) -> Self;
+}
+
 // IMPORTANT: This is a hack!
 // We use this trait purely to transmute half::f16 into f16 type to make sure that rustc emits
 // llvm assemblt. If we use half::f16, the library will emit its own x86 inline assembly,
@@ -517,3 +554,40 @@ macro_rules! int_to_float {
 }
 
 int_to_float!([i16, u16, i32, u32]);
+
+impl HostConvert<bf16> for f32 {
+    fn convert(self, rnd: Rounding, ftz: bool, sat: bool, expected: bf16) -> Result<(), bf16> {
+        assert!(rnd == Rounding::Rn && !ftz && !sat);
+        // Software conversion of the half crate, which rounds to nearest even
+        let host_result = bf16::from_f32(self);
+        if (host_result.is_nan() && expected.is_nan()) || host_result.to_bits() == expected.to_bits() {
+            Ok(())
+        } else {
+            Err(host_result)
+        }
+    }
+}
+
+impl HostRelu<bf16> for f32 {
+    // Rounding to nearest keeps the sign, negative inputs and only those give negative results
+    fn relu(self) -> Self {
+        if self.is_sign_negative() && !self.is_nan() {
+            0.0
+        } else {
+            self
+        }
+    }
+}
+
+impl HostConvert<f32> for bf16 {
+    fn convert(self, rnd: Rounding, ftz: bool, sat: bool, expected: f32) -> Result<(), f32> {
+        assert!(rnd == Rounding::Default && !ftz && !sat);
+        // Exact, bf16 is the upper half of f32
+        let host_result = self.to_f32();
+        if (host_result.is_nan() && expected.is_nan()) || host_result.to_bits() == expected.to_bits() {
+            Ok(())
+        } else {
+            Err(host_result)
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::common;
+use crate::test::{make_random, make_range, Inputs, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
+use num::{cast::AsPrimitive, PrimInt};
+use rand::{distributions::Standard, prelude::Distribution, Rng};
+
+pub static PTX: &str = include_str!("divrem.ptx");
+
+pub fn all_tests() -> Vec<TestCase> {
+    vec![
+        div_rem_range::<u16>(),
+        div_rem_range::<i16>(),
+        div_rem_rng::<u32>(),
+        div_rem_rng::<i32>(),
+        div_rem_rng::<u64>(),
+        div_rem_rng::<i64>(),
+    ]
+}
+
+fn div_rem_range<T: PtxScalar + PrimInt + AsPrimitive<i128>>() -> TestCase
+where
+    i128: AsPrimitive<T>,
+{
+    let name = format!("div_rem_{}", T::name());
+    let tags = div_rem_tags::<T>(Inputs::Exhaustive);
+    TestCase::new(name, tags, make_range(DivRem::<T> { _phantom: std::marker::PhantomData }))
+}
+
+fn div_rem_rng<T: PtxScalar + PrimInt + AsPrimitive<i128>>() -> TestCase
+where
+    i128: AsPrimitive<T>,
+    Standard: Distribution<T>,
+{
+    let name = format!("div_rem_{}", T::name());
+    let tags = div_rem_tags::<T>(Inputs::Random);
+    TestCase::new(name, tags, make_random(DivRem::<T> { _phantom: std::marker::PhantomData }))
+}
+
+fn div_rem_tags<T: PtxScalar>(inputs: Inputs) -> Tags {
+    // Tests both instructions at once, tagged with either
+    Tags { instruction: "div", types: vec![T::name().to_string()], modifiers: vec!["rem"], inputs, ..Default::default() }
+}
+
+/// `div` and `rem` on the same operands, writing the quotient and the remainder.
+struct DivRem<T: PtxScalar> {
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T: PtxScalar> TestPtx for DivRem<T> {
+    fn body(&self) -> String {
+        PTX
+            .replace("<TYPE_SIZE>", &T::size_of().to_string())
+            .replace("<TYPE>", T::name())
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "output_quotient",
+            "output_remainder",
+        ]
+    }
+}
+
+impl<T: PtxScalar + PrimInt + AsPrimitive<i128>> TestCommon for DivRem<T>
+where
+    i128: AsPrimitive<T>,
+{
+    type Input = (T, T);
+    type Output = (T, T);
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let (a, b) = input;
+        // Results of division by zero are unspecified
+        if b.is_zero() {
+            return Ok(());
+        }
+        // Computed on wider integers, MIN / -1 wraps around just like on the GPU
+        let (a, b): (i128, i128) = (a.as_(), b.as_());
+        let expected = ((a / b).as_(), (a % b).as_());
+        if expected == output {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl<T: PtxScalar + PrimInt + AsPrimitive<i128>> RangeTest for DivRem<T>
+where
+    i128: AsPrimitive<T>,
+{
+    fn generate(&self, input: u32) -> Self::Input {
+        common::halves(input)
+    }
+}
+
+impl<T: PtxScalar + PrimInt + AsPrimitive<i128>> RandomTest for DivRem<T>
+where
+    i128: AsPrimitive<T>,
+    Standard: Distribution<T>,
+{
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        (rng.gen(), rng.gen())
+    }
+}
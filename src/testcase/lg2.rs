@@ -1,7 +1,7 @@
 use rug::Float;
 
 use crate::common::{self, flush_to_zero_f32};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use core::f32;
 use std::mem;
 
@@ -20,8 +20,9 @@ fn lg2(ftz: bool) -> TestCase {
     let mut tolerance = Float::with_val(PRECISION, -22.6f64);
     tolerance.exp2_mut();
     let test = make_range(Lg2 { ftz, tolerance });
+    let tags = Tags { instruction: "lg2", types: vec!["f32".to_string()], rounding: Some("approx"), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("lg2_approx{}", ftz), test)
+    TestCase::new(format!("lg2_approx{}", ftz), tags, test)
 }
 
 pub struct Lg2 {
@@ -1,5 +1,10 @@
-use crate::{common, test::{make_range, RangeTest, TestCase, TestCommon, TestPtx}};
-use std::mem;
+use super::arith::{ArithFloat, Value};
+use crate::common;
+use crate::test::{make_random, make_range, Inputs, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
+use half::{bf16, f16};
+use num::{Float, NumCast};
+use rand::Rng;
+use std::marker::PhantomData;
 
 pub static PTX: &str = include_str!("minmax.ptx");
 
@@ -7,53 +12,80 @@ pub fn all_tests() -> Vec<TestCase> {
     let mut tests = Vec::new();
     for ftz in [false, true] {
         for nan in [false, true] {
-            tests.push(min(ftz, nan));
-            tests.push(max(ftz, nan));
+            tests.push(min_max_range::<f16>(false, ftz, nan));
+            tests.push(min_max_range::<f16>(true, ftz, nan));
+        }
+    }
+    // bf16 has no .ftz
+    for nan in [false, true] {
+        for max in [false, true] {
+            tests.push(min_max_range::<bf16>(max, false, nan));
+            tests.push(min_max_rng::<[bf16; 2]>(max, false, nan));
         }
     }
     tests
 }
 
-fn min(ftz: bool, nan: bool) -> TestCase {
-    let name = format!(
-        "min{}{}",
-        if ftz { "_ftz" } else { "" },
-        if nan { "_nan" } else { "" }
-    );
-    TestCase::new(
-        name.to_string(),
-        make_range(Min { ftz, nan }),
-    )
+fn min_max_range<V: Value>(max: bool, ftz: bool, nan: bool) -> TestCase
+where
+    MinMax<V>: RangeTest,
+{
+    let test = MinMax::<V> { max, ftz, nan, _phantom: PhantomData };
+    TestCase::new(test.name(), test.tags(Inputs::Exhaustive), make_range(test))
 }
 
-fn max(ftz: bool, nan: bool) -> TestCase {
-    let name = format!(
-        "max{}{}",
-        if ftz { "_ftz" } else { "" },
-        if nan { "_nan" } else { "" }
-    );
-    TestCase::new(
-        name.to_string(),
-        make_range(Max { ftz, nan }),
-    )
+fn min_max_rng<V: Value>(max: bool, ftz: bool, nan: bool) -> TestCase {
+    let test = MinMax::<V> { max, ftz, nan, _phantom: PhantomData };
+    TestCase::new(test.name(), test.tags(Inputs::Random), make_random(test))
 }
 
-struct Min {
+/// `min` or `max` of two operands, every lane of packed types is checked independently.
+struct MinMax<V> {
+    max: bool,
     ftz: bool,
     nan: bool,
+    _phantom: PhantomData<V>,
+}
+
+impl<V: Value> MinMax<V> {
+    fn instruction(&self) -> &'static str {
+        if self.max {
+            "max"
+        } else {
+            "min"
+        }
+    }
+
+    fn name(&self) -> String {
+        let ftz = if self.ftz { "_ftz" } else { "" };
+        let nan = if self.nan { "_nan" } else { "" };
+        // Tests of f16 predate all other types and are named without the type
+        let ty = if V::name() == "f16" { String::new() } else { format!("_{}", V::name()) };
+        format!("{}{}{}{}", self.instruction(), ftz, nan, ty)
+    }
+
+    fn tags(&self, inputs: Inputs) -> Tags {
+        let modifiers = if self.nan { vec!["nan"] } else { Vec::new() };
+        Tags { instruction: self.instruction(), types: vec![V::name()], ftz: self.ftz, modifiers, inputs, ..Default::default() }
+    }
 }
 
-impl TestPtx for Min {
+impl<V: Value> TestPtx for MinMax<V> {
     fn body(&self) -> String {
         let name = format!(
-            "min{}{}.f16",
+            "{}{}{}.{}",
+            self.instruction(),
             if self.ftz { ".ftz" } else { "" },
-            if self.nan { ".NaN" } else { "" }
+            if self.nan { ".NaN" } else { "" },
+            V::name()
         );
+        let btype = format!("b{}", V::size_of() * 8);
+        let reg_type = if V::Lane::untyped_registers() { btype.clone() } else { V::name() };
         PTX
-            .replace("<TYPE_SIZE>", "2")
-            .replace("<TYPE>", "f16")
-            .replace("<BTYPE>", "b16")
+            .replace("<REG_TYPE>", &reg_type)
+            .replace("<TYPE_SIZE>", &V::size_of().to_string())
+            .replace("<TYPE>", &V::name())
+            .replace("<BTYPE>", &btype)
             .replace("<OP>", &name)
     }
 
@@ -66,16 +98,14 @@ impl TestPtx for Min {
     }
 }
 
-impl TestCommon for Min {
-    type Input = (half::f16, half::f16);
-    type Output = half::f16;
+impl<V: Value> TestCommon for MinMax<V> {
+    type Input = (V, V);
+    type Output = V;
 
     fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
         let (a, b) = input;
-        let expected = minmax_host(a, b, self.nan, self.ftz, f16::min);
-        if (expected.is_nan() && output.is_nan())
-            || (expected.to_ne_bytes() == output.to_ne_bytes())
-        {
+        let expected = V::from_lanes(|i| minmax_host(a.lane(i), b.lane(i), self.max, self.nan, self.ftz));
+        if expected.matches(output) {
             Ok(())
         } else {
             Err(expected)
@@ -83,86 +113,53 @@ impl TestCommon for Min {
     }
 }
 
-impl RangeTest for Min {
+impl RangeTest for MinMax<f16> {
     fn generate(&self, input: u32) -> Self::Input {
-        unsafe { mem::transmute::<_, (half::f16, half::f16)>(input) }
+        common::halves(input)
     }
 }
 
-struct Max {
-    ftz: bool,
-    nan: bool,
-}
-
-impl TestPtx for Max {
-    fn body(&self) -> String {
-        let name = format!(
-            "max{}{}.f16",
-            if self.ftz { ".ftz" } else { "" },
-            if self.nan { ".NaN" } else { "" }
-        );
-        PTX
-            .replace("<TYPE_SIZE>", "2")
-            .replace("<TYPE>", "f16")
-            .replace("<BTYPE>", "b16")
-            .replace("<OP>", &name)
-    }
-
-    fn args(&self) -> &[&str] {
-        &[
-            "input_a",
-            "input_b",
-            "output",
-        ]
+impl RangeTest for MinMax<bf16> {
+    fn generate(&self, input: u32) -> Self::Input {
+        common::halves(input)
     }
 }
 
-impl TestCommon for Max {
-    type Input = (half::f16, half::f16);
-    type Output = half::f16;
-
-    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
-        let (a, b) = input;
-        let expected = minmax_host(a, b, self.nan, self.ftz, f16::max);
-        if (expected.is_nan() && output.is_nan())
-            || (expected.to_ne_bytes() == output.to_ne_bytes())
-        {
-            Ok(())
-        } else {
-            Err(expected)
+impl<V: Value> RandomTest for MinMax<V> {
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        // Packed values have two lanes at most, a fixed array avoids allocating for every input
+        let zero = <V::Lane as num::Zero>::zero();
+        let mut lanes = [(zero, zero); 2];
+        for lane in &mut lanes[..V::LANES] {
+            *lane = random_operands::<V::Lane, R>(rng);
         }
+        (V::from_lanes(|i| lanes[i].0), V::from_lanes(|i| lanes[i].1))
     }
 }
 
-impl RangeTest for Max {
-    fn generate(&self, input: u32) -> Self::Input {
-        unsafe { mem::transmute::<_, (half::f16, half::f16)>(input) }
-    }
+/// Any bit patterns, a quarter of them with operands of the same magnitude and either sign.
+fn random_operands<T: ArithFloat, R: Rng>(rng: &mut R) -> (T, T) {
+    let a = T::from_bits(rng.gen());
+    let b = match rng.gen_range(0..8) {
+        0 => a,
+        1 => -a,
+        _ => T::from_bits(rng.gen()),
+    };
+    (a, b)
 }
 
-fn minmax_host(
-    mut a: half::f16,
-    mut b: half::f16,
-    nan: bool,
-    ftz: bool,
-    fn_: fn(f16, f16) -> f16,
-) -> half::f16 {
-    common::flush_to_zero_f16(&mut a, ftz);
-    common::flush_to_zero_f16(&mut b, ftz);
-    if a.is_nan() && b.is_nan() {
-        half::f16::NAN
-    } else if nan && (a.is_nan() || b.is_nan()) {
-        half::f16::NAN
+fn minmax_host<T: ArithFloat>(mut a: T, mut b: T, max: bool, nan: bool, ftz: bool) -> T {
+    a.flush_to_zero(ftz);
+    b.flush_to_zero(ftz);
+    if (a.is_nan() && b.is_nan()) || (nan && (a.is_nan() || b.is_nan())) {
+        <T as Float>::nan()
     } else if a.is_nan() {
         b
     } else if b.is_nan() {
         a
     } else {
-        unsafe {
-            mem::transmute(fn_(
-                mem::transmute::<_, f16>(a),
-                mem::transmute::<_, f16>(b),
-            ))
-        }
+        // Every f16 and bf16 is exact in f32
+        let (a, b) = (a.to_f32().unwrap(), b.to_f32().unwrap());
+        <T as NumCast>::from(if max { a.max(b) } else { a.min(b) }).unwrap()
     }
 }
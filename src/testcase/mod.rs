@@ -1,183 +1,321 @@
-use std::{alloc::{alloc, dealloc, Layout}, ffi::{CStr, CString}, ptr};
+use std::{alloc::{alloc, dealloc, Layout}, ffi::{CStr, CString}, path::{Path, PathBuf}, ptr};
 
-use crate::{cuda::Cuda, nvrtc::Nvrtc, test::{TestCase, TestPtx}};
+use crate::{common, cuda::Cuda, golden::Golden, nvrtc::Nvrtc, show, test::{TestCase, TestPtx, TimeLimit}};
 
+mod arith;
 mod bfe;
 mod bfi;
 mod brev;
 mod cos;
 mod cvt;
+mod divrem;
 mod lg2;
 mod minmax;
+mod mul;
 mod rcp;
 mod rsqrt;
+mod setp;
 mod shift;
 mod sin;
 mod sqrt;
 
 pub trait TestContext {
     fn cuda(&self) -> &Cuda;
-    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String>;
+    /// CUDA library whose results are used as expected values in differential mode.
+    fn reference_cuda(&self) -> Option<&Cuda>;
+    fn golden(&self) -> Option<Golden<'_>>;
+    /// Directory with corpus files, replacing default inputs of tests that have one
+    fn corpus(&self) -> Option<&Path>;
+    /// Seed of random inputs, replaced by the recorded one when replaying
+    fn seed(&self) -> u64;
+    /// Number of random inputs of every random test
+    fn random_count(&self) -> usize;
+    /// How long tests may run before they stop and report partial coverage
+    fn time_limit(&self) -> TimeLimit;
+    /// Directory to write reproducers of mismatching results into
+    fn repro_dir(&self) -> Option<&Path>;
+    /// Source of the module loaded into CUDA for the kernel `name`, dumped if requested
+    fn prepare_test_source(&self, name: &str, ptx: &dyn TestPtx) -> Result<CString, String>;
 }
 
 pub struct TestFixture<L> {
     pub libs: L,
+    pub reference: Option<Cuda>,
+    /// Directory to record outputs of the tested CUDA library into
+    pub record: Option<PathBuf>,
+    pub corpus: Option<PathBuf>,
+    pub seed: u64,
+    pub random_count: usize,
+    pub time_limit: TimeLimit,
+    /// Directory to write sources of every kernel into, see `show::dump`
+    pub dump_dir: Option<PathBuf>,
+    /// Directory to write reproducers of mismatching results into, see `repro::write`
+    pub repro_dir: Option<PathBuf>,
+}
+
+/// Context for verifying outputs recorded into golden files, without loading any CUDA library.
+pub struct ReplayFixture {
+    pub dir: PathBuf,
+    pub corpus: Option<PathBuf>,
+    pub random_count: usize,
+    pub time_limit: TimeLimit,
+}
+
+impl TestContext for ReplayFixture {
+    fn cuda(&self) -> &Cuda {
+        unreachable!("tests don't use CUDA when replaying recorded outputs")
+    }
+
+    fn reference_cuda(&self) -> Option<&Cuda> {
+        None
+    }
+
+    fn golden(&self) -> Option<Golden<'_>> {
+        Some(Golden::Replay(&self.dir))
+    }
+
+    fn corpus(&self) -> Option<&Path> {
+        self.corpus.as_deref()
+    }
+
+    fn seed(&self) -> u64 {
+        // Random inputs are reseeded with the seed recorded in the golden file
+        0
+    }
+
+    fn random_count(&self) -> usize {
+        self.random_count
+    }
+
+    fn time_limit(&self) -> TimeLimit {
+        self.time_limit
+    }
+
+    fn repro_dir(&self) -> Option<&Path> {
+        // There is no module to reproduce mismatches with, tests aren't compiled
+        None
+    }
+
+    fn prepare_test_source(&self, _name: &str, _ptx: &dyn TestPtx) -> Result<CString, String> {
+        unreachable!("tests aren't compiled when replaying recorded outputs")
+    }
 }
 
 const PTX_HEADER: &'_ str = "
-    .version 7.0
+    .version 7.1
     .target sm_80
     .address_size 64
 ";
 
-impl TestContext for TestFixture<(Cuda,)> {
-    fn cuda(&self) -> &Cuda {
-        &self.libs.0
+/// Generate a PTX module with a test kernel `run` taking `args` and executing `body`.
+pub fn ptx_source(args: &[&str], body: &str) -> String {
+    /// Generate PTX test function signature.
+    fn fmt_ptx_signature(args: &[&str]) -> String {
+        let args: Vec<_> = args.iter().map(|a| format!(".param .u64 {}", a)).collect();
+        format!(".entry run({})", args.join(", "))
     }
 
-    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
-        /// Generate PTX test function signature.
-        fn fmt_ptx_signature(args: &[&str]) -> String {
-            let args: Vec<_> = args.iter().map(|a| format!(".param .u64 {}", a)).collect();
-            format!(".entry run({})", args.join(", "))
+    /// Generate PTX to load values of test function parameters.
+    fn fmt_ptx_params_load(args: &[&str]) -> String {
+        let mut text = String::new();
+        for arg in args {
+            text.push_str(&format!(".reg .u64    {name}_addr;\n", name = arg));
+            text.push_str(&format!("ld.param.u64 {name}_addr, [{name}];\n", name = arg));
         }
-
-        /// Generate PTX to load values of test function parameters.
-        fn fmt_ptx_params_load(args: &[&str]) -> String {
-            let mut text = String::new();
-            for arg in args {
-                text.push_str(&format!(".reg .u64    {name}_addr;\n", name = arg));
-                text.push_str(&format!("ld.param.u64 {name}_addr, [{name}];\n", name = arg));
-            }
-            text
-        }
-
-        Ok(CString::new(format!(
-            "{}\n{}\n{{\n{}\n{}\nret;\n}}",
-            PTX_HEADER,
-            fmt_ptx_signature(ptx.args()),
-            fmt_ptx_params_load(ptx.args()),
-            ptx.body(),
-        )).unwrap())
+        text
     }
+
+    format!(
+        "{}\n{}\n{{\n{}\n{}\nret;\n}}",
+        PTX_HEADER,
+        fmt_ptx_signature(args),
+        fmt_ptx_params_load(args),
+        body,
+    )
 }
 
-impl TestContext for TestFixture<(Cuda, Nvrtc)> {
-    fn cuda(&self) -> &Cuda {
-        &self.libs.0
+/// Generate CUDA source of a test kernel `run` taking `args` and executing `body` as inline PTX.
+pub fn cuda_source(args: &[&str], body: &str) -> String {
+    /// Generate CUDA test function signature.
+    fn fmt_cuda_signature(args: &[&str]) -> String {
+        let args: Vec<_> = args.iter().map(|a| format!("unsigned long long * {}", a)).collect();
+        format!("extern \"C\" __global__ void run({})", args.join(", "))
     }
 
-    fn prepare_test_source(&self, ptx: &dyn TestPtx) -> Result<CString, String> {
-        /// Generate CUDA test function signature.
-        fn fmt_cuda_signature(args: &[&str]) -> String {
-            let args: Vec<_> = args.iter().map(|a| format!("unsigned long long * {}", a)).collect();
-            format!("extern \"C\" __global__ void run({})", args.join(", "))
+    /// Generate PTX to load values of test function parameters.
+    fn fmt_cuda_inline_ptx_params_load(args: &[&str]) -> String {
+        let mut text = String::new();
+        for (arg_index, arg_name) in args.iter().enumerate() {
+            text.push_str(&format!(".reg .u64 {name}_addr;\n", name = arg_name));
+            text.push_str(&format!("mov.u64   {name}_addr, %{index};\n", name = arg_name, index = arg_index));
         }
+        text
+    }
 
-        /// Generate PTX to load values of test function parameters.
-        fn fmt_cuda_inline_ptx_params_load(args: &[&str]) -> String {
-            let mut text = String::new();
-            for (arg_index, arg_name) in args.iter().enumerate() {
-                text.push_str(&format!(".reg .u64 {name}_addr;\n", name = arg_name));
-                text.push_str(&format!("mov.u64   {name}_addr, %{index};\n", name = arg_name, index = arg_index));
-            }
-            text
-        }
+    /// Generate CUDA parameter list for inline PTX.
+    fn fmt_cuda_inline_ptx_params(args: &[&str]) -> String {
+        args.iter().map(|a| format!(r#""l"({})"#, a)).collect::<Vec<_>>().join(", ")
+    }
 
-        /// Generate CUDA parameter list for inline PTX.
-        fn fmt_cuda_inline_ptx_params(args: &[&str]) -> String {
-            args.iter().map(|a| format!(r#""l"({})"#, a)).collect::<Vec<_>>().join(", ")
-        }
+    /// Transform raw PTX into CUDA inline PTX function body.
+    fn ptx_to_inline(args: &[&str], body: &str) -> String {
+        let mut body = body.to_string();
 
-        /// Transform raw PTX into CUDA inline PTX function body.
-        fn ptx_to_inline(args: &[&str], body: &str) -> String {
-            let mut body = body.to_string();
+        // Escape "%" (used for things like %tid (thread id) etc.)
+        body = body.replace("%", "%%");
 
-            // Escape "%" (used for things like %tid (thread id) etc.)
-            body = body.replace("%", "%%");
+        body = format!(
+            "{}\n{}",
+            fmt_cuda_inline_ptx_params_load(args),
+            body,
+        );
 
-            body = format!(
-                "{}\n{}",
-                fmt_cuda_inline_ptx_params_load(args),
-                body,
-            );
+        body = body.lines().map(|l| format!("\"{}\"\n", l)).collect::<Vec<_>>().join("    ");
 
-            body = body.lines().map(|l| format!("\"{}\"\n", l)).collect::<Vec<_>>().join("    ");
+        format!(
+            "asm({}    :: {});",
+            body,
+            fmt_cuda_inline_ptx_params(args),
+        )
+    }
 
-            format!(
-                "asm({}    :: {});",
-                body,
-                fmt_cuda_inline_ptx_params(args),
-            )
-        }
+    format!(
+        "{} {{\n{}\n}}",
+        fmt_cuda_signature(args),
+        ptx_to_inline(args, body),
+    )
+}
 
-        let nvrtc = &self.libs.1;
+/// Compile CUDA source with NVRTC into PTX.
+pub fn compile_cuda(nvrtc: &Nvrtc, source_cuda: &str) -> Result<CString, String> {
+    let source_cuda_c = CString::new(source_cuda).unwrap();
 
-        let source_cuda = format!(
-            "{} {{\n{}\n}}",
-            fmt_cuda_signature(ptx.args()),
-            ptx_to_inline(ptx.args(), &ptx.body()),
-        );
-        let source_cuda_c = CString::new(source_cuda.clone()).unwrap();
+    let mut program = ptr::null_mut();
+    unsafe { nvrtc.nvrtcCreateProgram(&mut program, source_cuda_c.as_ptr() as _, ptr::null() as _, 0, ptr::null(), ptr::null()) }.unwrap();
 
-        let mut program = ptr::null_mut();
-        unsafe { nvrtc.nvrtcCreateProgram(&mut program, source_cuda_c.as_ptr() as _, ptr::null() as _, 0, ptr::null(), ptr::null()) }.unwrap();
+    let options = [
+        "-arch=sm_80",
+    ].map(|opt| CString::new(opt).unwrap());
+    let options_c: Vec<_> = options.iter().map(|opt| opt.as_c_str().as_ptr()).collect();
 
-        let options = [
-            "-arch=sm_80",
-        ].map(|opt| CString::new(opt).unwrap());
-        let options_c: Vec<_> = options.iter().map(|opt| opt.as_c_str().as_ptr()).collect();
+    let result = unsafe { nvrtc.nvrtcCompileProgram(program, options_c.len() as _, options_c.as_ptr() as _) };
 
-        let result = unsafe { nvrtc.nvrtcCompileProgram(program, options_c.len() as _, options_c.as_ptr() as _) };
+    if result.is_err() {
+        let error = unsafe { CStr::from_ptr(nvrtc.nvrtcGetErrorString(result)) };
+        let error = String::from_utf8_lossy(error.to_bytes()).to_string();
 
-        if result.is_err() {
-            let error = unsafe { CStr::from_ptr(nvrtc.nvrtcGetErrorString(result)) };
-            let error = String::from_utf8_lossy(error.to_bytes()).to_string();
+        let mut log_size = 0;
+        unsafe { nvrtc.nvrtcGetProgramLogSize(program, &mut log_size) }.unwrap();
 
-            let mut log_size = 0;
-            unsafe { nvrtc.nvrtcGetProgramLogSize(program, &mut log_size) }.unwrap();
+        let log_layout = Layout::array::<core::ffi::c_char>(log_size).unwrap();
+        let log_buffer = unsafe { alloc(log_layout) };
 
-            let log_layout = Layout::array::<core::ffi::c_char>(log_size).unwrap();
-            let log_buffer = unsafe { alloc(log_layout) };
+        unsafe { nvrtc.nvrtcGetProgramLog(program, log_buffer as _) }.unwrap();
 
-            unsafe { nvrtc.nvrtcGetProgramLog(program, log_buffer as _) }.unwrap();
+        let log_cstr = unsafe { CStr::from_ptr(log_buffer as _) };
+        let log = String::from_utf8_lossy(log_cstr.to_bytes()).to_string();
 
-            let log_cstr = unsafe { CStr::from_ptr(log_buffer as _) };
-            let log = String::from_utf8_lossy(log_cstr.to_bytes()).to_string();
+        unsafe { dealloc(log_buffer, log_layout) };
 
-            unsafe { dealloc(log_buffer, log_layout) };
+        let error = format!("NVRTC error: {error}");
+        let log = format!("Compilation produced the following log:\n{log}");
+        let source = format!("Program source:\n{}", common::numbered_lines(source_cuda));
 
-            let error = format!("NVRTC error: {error}");
-            let log = format!("Compilation produced the following log:\n{log}");
-            let mut source = format!("Program source:\n");
-            for (i, line) in source_cuda.lines().enumerate() {
-                source.push_str(&format!("{:3} | {}\n", i + 1, line));
-            }
+        return Err(format!("{error}\n{log}\n{source}"));
+    }
 
-            return Err(format!("{error}\n{log}\n{source}"));
-        }
+    let mut ptx_size = 0;
+    unsafe { nvrtc.nvrtcGetPTXSize(program, &mut ptx_size) }.unwrap();
+
+    let source_ptx_layout = Layout::array::<core::ffi::c_char>(ptx_size).unwrap();
+    let source_ptx_buffer = unsafe { alloc(source_ptx_layout) };
+
+    unsafe { nvrtc.nvrtcGetPTX(program, source_ptx_buffer as _ ) }.unwrap();
 
-        let mut ptx_size = 0;
-        unsafe { nvrtc.nvrtcGetPTXSize(program, &mut ptx_size) }.unwrap();
+    let source_ptx = unsafe { CStr::from_ptr(source_ptx_buffer as _) };
+    let source_ptx = source_ptx.to_owned();
 
-        let source_ptx_layout = Layout::array::<core::ffi::c_char>(ptx_size).unwrap();
-        let source_ptx_buffer = unsafe { alloc(source_ptx_layout) };
+    unsafe { nvrtc.nvrtcDestroyProgram(&mut program) }.unwrap();
 
-        unsafe { nvrtc.nvrtcGetPTX(program, source_ptx_buffer as _ ) }.unwrap();
+    unsafe { dealloc(source_ptx_buffer, source_ptx_layout) };
+
+    Ok(source_ptx)
+}
+
+/// CUDA library under test, together with the compiler preparing module sources for it, if any.
+pub trait Libraries {
+    fn cuda(&self) -> &Cuda;
+    /// Source of the module loaded into CUDA for the kernel `name`, dumped into `dump_dir`
+    fn prepare_test_source(&self, name: &str, ptx: &dyn TestPtx, dump_dir: Option<&Path>) -> Result<CString, String>;
+}
 
-        let source_ptx = unsafe { CStr::from_ptr(source_ptx_buffer as _) };
-        let source_ptx = source_ptx.to_owned();
+impl Libraries for (Cuda,) {
+    fn cuda(&self) -> &Cuda {
+        &self.0
+    }
 
-        unsafe { nvrtc.nvrtcDestroyProgram(&mut program) }.unwrap();
+    fn prepare_test_source(&self, name: &str, ptx: &dyn TestPtx, dump_dir: Option<&Path>) -> Result<CString, String> {
+        let source = ptx_source(ptx.args(), &ptx.body());
+        show::dump(dump_dir, name, "ptx", &source);
+        Ok(CString::new(source).unwrap())
+    }
+}
 
-        unsafe { dealloc(source_ptx_buffer, source_ptx_layout) };
+impl Libraries for (Cuda, Nvrtc) {
+    fn cuda(&self) -> &Cuda {
+        &self.0
+    }
 
+    fn prepare_test_source(&self, name: &str, ptx: &dyn TestPtx, dump_dir: Option<&Path>) -> Result<CString, String> {
+        let source_cuda = cuda_source(ptx.args(), &ptx.body());
+        show::dump(dump_dir, name, "cu", &source_cuda);
+        let source_ptx = compile_cuda(&self.1, &source_cuda)?;
+        show::dump(dump_dir, name, "ptx", &source_ptx.to_string_lossy());
         Ok(source_ptx)
     }
 }
 
+impl<L: Libraries> TestContext for TestFixture<L> {
+    fn cuda(&self) -> &Cuda {
+        self.libs.cuda()
+    }
+
+    fn reference_cuda(&self) -> Option<&Cuda> {
+        self.reference.as_ref()
+    }
+
+    fn golden(&self) -> Option<Golden<'_>> {
+        self.record.as_deref().map(Golden::Record)
+    }
+
+    fn corpus(&self) -> Option<&Path> {
+        self.corpus.as_deref()
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn random_count(&self) -> usize {
+        self.random_count
+    }
+
+    fn time_limit(&self) -> TimeLimit {
+        self.time_limit
+    }
+
+    fn repro_dir(&self) -> Option<&Path> {
+        self.repro_dir.as_deref()
+    }
+
+    fn prepare_test_source(&self, name: &str, ptx: &dyn TestPtx) -> Result<CString, String> {
+        self.libs.prepare_test_source(name, ptx, self.dump_dir.as_deref())
+    }
+}
+
 pub fn tests() -> Vec<TestCase> {
     let mut tests = vec![];
+    tests.extend(arith::all_tests());
     tests.extend(bfe::all_tests());
     tests.extend(bfi::all_tests());
     tests.extend(brev::all_tests());
@@ -190,6 +328,9 @@ pub fn tests() -> Vec<TestCase> {
     tests.extend(sin::all_tests());
     tests.extend(cos::all_tests());
     tests.extend(lg2::all_tests());
+    tests.extend(setp::all_tests());
+    tests.extend(divrem::all_tests());
+    tests.extend(mul::all_tests());
 
     tests.sort_unstable_by_key(|t| t.name.clone());
 
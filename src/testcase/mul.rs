@@ -0,0 +1,278 @@
+use crate::common;
+use crate::test::{make_random, make_range, Inputs, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
+use num::cast::AsPrimitive;
+use rand::{distributions::Standard, prelude::Distribution, Rng};
+use std::marker::PhantomData;
+
+pub static MUL_PTX: &str = include_str!("mul.ptx");
+pub static MAD_PTX: &str = include_str!("mad.ptx");
+
+pub fn all_tests() -> Vec<TestCase> {
+    let mut tests = Vec::new();
+    for mode in [Mode::Lo, Mode::Hi] {
+        tests.extend(range_tests::<u16, u16>(mode));
+        tests.extend(range_tests::<i16, i16>(mode));
+        tests.extend(random_tests::<u32, u32>(mode));
+        tests.extend(random_tests::<i32, i32>(mode));
+        tests.extend(random_tests::<u64, u64>(mode));
+        tests.extend(random_tests::<i64, i64>(mode));
+    }
+    // Results of .wide are twice as wide as the operands, so there is none for 64-bit types
+    tests.extend(range_tests::<u16, u32>(Mode::Wide));
+    tests.extend(range_tests::<i16, i32>(Mode::Wide));
+    tests.extend(random_tests::<u32, u64>(Mode::Wide));
+    tests.extend(random_tests::<i32, i64>(Mode::Wide));
+    let hi_sat = Modifiers { mode: Mode::Hi, sat: true };
+    tests.push(TestCase::new(
+        hi_sat.name::<i32>("mad"),
+        hi_sat.tags::<i32, i32>("mad", Inputs::Random),
+        make_random(Mad::<i32, i32>::new(hi_sat)),
+    ));
+    tests
+}
+
+/// `mul` and `mad` on every pair of 16-bit operands.
+fn range_tests<T: Int, D: Int>(mode: Mode) -> [TestCase; 2] {
+    let modifiers = Modifiers { mode, sat: false };
+    [
+        TestCase::new(
+            modifiers.name::<T>("mul"),
+            modifiers.tags::<T, D>("mul", Inputs::Exhaustive),
+            make_range(Mul::<T, D>::new(modifiers)),
+        ),
+        TestCase::new(
+            modifiers.name::<T>("mad"),
+            modifiers.tags::<T, D>("mad", Inputs::Exhaustive),
+            make_range(Mad::<T, D>::new(modifiers)),
+        ),
+    ]
+}
+
+fn random_tests<T: Int, D: Int>(mode: Mode) -> [TestCase; 2]
+where
+    Standard: Distribution<T> + Distribution<D>,
+{
+    let modifiers = Modifiers { mode, sat: false };
+    [
+        TestCase::new(
+            modifiers.name::<T>("mul"),
+            modifiers.tags::<T, D>("mul", Inputs::Random),
+            make_random(Mul::<T, D>::new(modifiers)),
+        ),
+        TestCase::new(
+            modifiers.name::<T>("mad"),
+            modifiers.tags::<T, D>("mad", Inputs::Random),
+            make_random(Mad::<T, D>::new(modifiers)),
+        ),
+    ]
+}
+
+/// Integer operand or result, which the host reference computes with on `i128`.
+trait Int: PtxScalar + AsPrimitive<i128> {
+    /// Lowest bits of `value`, just like the GPU drops the bits that don't fit into a register.
+    fn truncate(value: i128) -> Self;
+}
+
+impl<T: PtxScalar + AsPrimitive<i128>> Int for T
+where
+    i128: AsPrimitive<T>,
+{
+    fn truncate(value: i128) -> Self {
+        value.as_()
+    }
+}
+
+/// Part of the full product written by `mul` and `mad`.
+#[derive(Clone, Copy)]
+enum Mode {
+    Lo,
+    Hi,
+    Wide,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Lo => "lo",
+            Mode::Hi => "hi",
+            Mode::Wide => "wide",
+        }
+    }
+}
+
+/// Modifiers of `mul` and `mad`, `sat` is only valid for `mad.hi.s32`.
+#[derive(Clone, Copy)]
+struct Modifiers {
+    mode: Mode,
+    sat: bool,
+}
+
+impl Modifiers {
+    fn name<T: PtxScalar>(self, instruction: &str) -> String {
+        let sat = if self.sat { "_sat" } else { "" };
+        format!("{}_{}{}_{}", instruction, self.mode.as_str(), sat, T::name())
+    }
+
+    fn tags<T: PtxScalar, D: PtxScalar>(self, instruction: &'static str, inputs: Inputs) -> Tags {
+        let mut types = vec![T::name().to_string()];
+        // Only .wide writes a result of another type than its operands
+        if D::name() != T::name() {
+            types.push(D::name().to_string());
+        }
+        Tags {
+            instruction,
+            types,
+            sat: self.sat,
+            modifiers: vec![self.mode.as_str()],
+            inputs,
+            ..Default::default()
+        }
+    }
+
+    /// PTX body of `template` with the instruction on operands of `T` writing a result of `D`.
+    fn body<T: PtxScalar, D: PtxScalar>(self, template: &str, instruction: &str) -> String {
+        let sat = if self.sat { ".sat" } else { "" };
+        let op = format!("{}.{}{}.{}", instruction, self.mode.as_str(), sat, T::name());
+        template
+            .replace("<OP>", &op)
+            .replace("<TYPE_SIZE>", &T::size_of().to_string())
+            .replace("<TYPE>", T::name())
+            .replace("<DTYPE_SIZE>", &D::size_of().to_string())
+            .replace("<DTYPE>", D::name())
+    }
+
+    /// Part of `a * b + c` selected by the mode, computed on `i128` and truncated to `D`.
+    fn host<T: Int, D: Int>(self, a: T, b: T, c: D) -> D {
+        let bits = T::size_of() * 8;
+        // The product of two u64 values overflows i128, but its lowest 128 bits are still exact
+        let product = a.as_().wrapping_mul(b.as_());
+        let result = match self.mode {
+            Mode::Lo | Mode::Wide => product.wrapping_add(c.as_()),
+            Mode::Hi => {
+                let high = if T::unsigned() { ((product as u128) >> bits) as i128 } else { product >> bits };
+                high + c.as_()
+            }
+        };
+        if self.sat {
+            D::truncate(result.clamp(i32::MIN as i128, i32::MAX as i128))
+        } else {
+            D::truncate(result)
+        }
+    }
+}
+
+/// `mul` of operands of `T`, writing a result of `D`, which is twice as wide for `.wide`.
+struct Mul<T, D> {
+    modifiers: Modifiers,
+    _phantom: PhantomData<(T, D)>,
+}
+
+impl<T, D> Mul<T, D> {
+    fn new(modifiers: Modifiers) -> Self {
+        Self { modifiers, _phantom: PhantomData }
+    }
+}
+
+impl<T: PtxScalar, D: PtxScalar> TestPtx for Mul<T, D> {
+    fn body(&self) -> String {
+        self.modifiers.body::<T, D>(MUL_PTX, "mul")
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "output",
+        ]
+    }
+}
+
+impl<T: Int, D: Int> TestCommon for Mul<T, D> {
+    type Input = (T, T);
+    type Output = D;
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let (a, b) = input;
+        let expected = self.modifiers.host(a, b, D::truncate(0));
+        if expected == output {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl<T: Int, D: Int> RangeTest for Mul<T, D> {
+    fn generate(&self, input: u32) -> Self::Input {
+        common::halves(input)
+    }
+}
+
+impl<T: Int, D: Int> RandomTest for Mul<T, D>
+where
+    Standard: Distribution<T>,
+{
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        (rng.gen(), rng.gen())
+    }
+}
+
+/// `mad` of operands of `T` and an addend of `D`, which is also the type of the result.
+struct Mad<T, D> {
+    modifiers: Modifiers,
+    _phantom: PhantomData<(T, D)>,
+}
+
+impl<T, D> Mad<T, D> {
+    fn new(modifiers: Modifiers) -> Self {
+        Self { modifiers, _phantom: PhantomData }
+    }
+}
+
+impl<T: PtxScalar, D: PtxScalar> TestPtx for Mad<T, D> {
+    fn body(&self) -> String {
+        self.modifiers.body::<T, D>(MAD_PTX, "mad")
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "input_c",
+            "output",
+        ]
+    }
+}
+
+impl<T: Int, D: Int> TestCommon for Mad<T, D> {
+    type Input = (T, T, D);
+    type Output = D;
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let (a, b, c) = input;
+        let expected = self.modifiers.host(a, b, c);
+        if expected == output {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl<T: Int, D: Int> RangeTest for Mad<T, D> {
+    fn generate(&self, input: u32) -> Self::Input {
+        let (a, b) = common::halves(input);
+        // Only operand pairs are exhaustive, the addend is a scrambled copy of both of them
+        // so that all of its bits vary
+        (a, b, D::truncate(input.wrapping_mul(0x9e37_79b9) as i128))
+    }
+}
+
+impl<T: Int, D: Int> RandomTest for Mad<T, D>
+where
+    Standard: Distribution<T> + Distribution<D>,
+{
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        (rng.gen(), rng.gen(), rng.gen())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::common::{flush_to_zero_f32, Rounding};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use std::mem;
 
 pub static PTX: &str = include_str!("rcp.ptx");
@@ -27,8 +27,9 @@ fn rcp_approx(ftz: bool) -> TestCase {
 fn rcp<const APPROX: bool>(rnd: Rounding, ftz: bool) -> TestCase {
     let test = make_range::<Rcp<APPROX>>(Rcp { rnd, ftz });
     let mode = if APPROX { "approx" } else { rnd.as_str() };
+    let tags = Tags { instruction: "rcp", types: vec!["f32".to_string()], rounding: Some(mode), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("rcp_{}{}", mode, ftz), test)
+    TestCase::new(format!("rcp_{}{}", mode, ftz), tags, test)
 }
 
 pub struct Rcp<const APPROX: bool> {
@@ -1,5 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use std::mem;
 
 pub static PTX: &str = include_str!("rsqrt.ptx");
@@ -14,8 +14,9 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn rsqrt_approx(ftz: bool) -> TestCase {
     let test = make_range(SqrtApprox { ftz });
+    let tags = Tags { instruction: "rsqrt", types: vec!["f32".to_string()], rounding: Some("approx"), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("rsqrt_approx{}", ftz), test)
+    TestCase::new(format!("rsqrt_approx{}", ftz), tags, test)
 }
 
 pub struct SqrtApprox {
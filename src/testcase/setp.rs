@@ -0,0 +1,173 @@
+use crate::common;
+use crate::test::{make_random, make_range, Inputs, PtxScalar, RandomTest, RangeTest, Tags, TestCase, TestCommon, TestPtx};
+use rand::Rng;
+use std::mem;
+
+pub static PTX: &str = include_str!("setp.ptx");
+
+pub fn all_tests() -> Vec<TestCase> {
+    let mut tests = Vec::new();
+    for cmp in Compare::INTEGER {
+        tests.push(setp_range::<u16>(cmp));
+        tests.push(setp_range::<i16>(cmp));
+        tests.push(setp_rng::<u32>(cmp));
+        tests.push(setp_rng::<i32>(cmp));
+    }
+    for cmp in Compare::FLOAT {
+        tests.push(setp_range::<half::f16>(cmp));
+        tests.push(setp_rng::<f32>(cmp));
+    }
+    tests
+}
+
+fn setp_range<T: PtxScalar + PartialOrd>(cmp: Compare) -> TestCase {
+    let name = format!("setp_{}_{}", cmp.as_ptx(), T::name());
+    let tags = setp_tags::<T>(cmp, Inputs::Exhaustive);
+    TestCase::new(name, tags, make_range(Setp::<T> { cmp, _phantom: std::marker::PhantomData }))
+}
+
+fn setp_rng<T: PtxScalar + PartialOrd>(cmp: Compare) -> TestCase {
+    let name = format!("setp_{}_{}", cmp.as_ptx(), T::name());
+    let tags = setp_tags::<T>(cmp, Inputs::Random);
+    TestCase::new(name, tags, make_random(Setp::<T> { cmp, _phantom: std::marker::PhantomData }))
+}
+
+fn setp_tags<T: PtxScalar>(cmp: Compare, inputs: Inputs) -> Tags {
+    Tags { instruction: "setp", types: vec![T::name().to_string()], modifiers: vec![cmp.as_ptx()], inputs, ..Default::default() }
+}
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Equ,
+    Neu,
+    Ltu,
+    Leu,
+    Gtu,
+    Geu,
+    Num,
+    Nan,
+}
+
+impl Compare {
+    const INTEGER: [Compare; 6] = [Compare::Eq, Compare::Ne, Compare::Lt, Compare::Le, Compare::Gt, Compare::Ge];
+
+    const FLOAT: [Compare; 14] = [
+        Compare::Eq,
+        Compare::Ne,
+        Compare::Lt,
+        Compare::Le,
+        Compare::Gt,
+        Compare::Ge,
+        Compare::Equ,
+        Compare::Neu,
+        Compare::Ltu,
+        Compare::Leu,
+        Compare::Gtu,
+        Compare::Geu,
+        Compare::Num,
+        Compare::Nan,
+    ];
+
+    fn as_ptx(self) -> &'static str {
+        match self {
+            Compare::Eq => "eq",
+            Compare::Ne => "ne",
+            Compare::Lt => "lt",
+            Compare::Le => "le",
+            Compare::Gt => "gt",
+            Compare::Ge => "ge",
+            Compare::Equ => "equ",
+            Compare::Neu => "neu",
+            Compare::Ltu => "ltu",
+            Compare::Leu => "leu",
+            Compare::Gtu => "gtu",
+            Compare::Geu => "geu",
+            Compare::Num => "num",
+            Compare::Nan => "nan",
+        }
+    }
+
+    fn host<T: PartialOrd>(self, a: T, b: T) -> bool {
+        // Only floats can be unordered, ordered comparisons are false if either operand is NaN
+        #[allow(clippy::eq_op)]
+        let unordered = a != a || b != b;
+        match self {
+            Compare::Eq => a == b,
+            Compare::Ne => !unordered && a != b,
+            Compare::Lt => a < b,
+            Compare::Le => a <= b,
+            Compare::Gt => a > b,
+            Compare::Ge => a >= b,
+            Compare::Equ => unordered || a == b,
+            Compare::Neu => a != b,
+            Compare::Ltu => unordered || a < b,
+            Compare::Leu => unordered || a <= b,
+            Compare::Gtu => unordered || a > b,
+            Compare::Geu => unordered || a >= b,
+            Compare::Num => !unordered,
+            Compare::Nan => unordered,
+        }
+    }
+}
+
+/// `setp` writing both the result of the comparison and its negation, `p|q`.
+struct Setp<T: PtxScalar> {
+    cmp: Compare,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<T: PtxScalar> TestPtx for Setp<T> {
+    fn body(&self) -> String {
+        let btype = if T::float() && T::size_of() == 2 { "b16" } else { T::name() };
+        PTX
+            .replace("<TYPE_SIZE>", &T::size_of().to_string())
+            .replace("<CMP>", self.cmp.as_ptx())
+            .replace("<TYPE>", T::name())
+            .replace("<BTYPE>", btype)
+    }
+
+    fn args(&self) -> &[&str] {
+        &[
+            "input_a",
+            "input_b",
+            "output_p",
+            "output_q",
+        ]
+    }
+}
+
+impl<T: PtxScalar + PartialOrd> TestCommon for Setp<T> {
+    type Input = (T, T);
+    type Output = (u8, u8);
+
+    fn host_verify(&self, input: Self::Input, output: Self::Output) -> Result<(), Self::Output> {
+        let (a, b) = input;
+        let p = self.cmp.host(a, b);
+        let expected = (p as u8, !p as u8);
+        if expected == output {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+}
+
+impl<T: PtxScalar + PartialOrd> RangeTest for Setp<T> {
+    fn generate(&self, input: u32) -> Self::Input {
+        common::halves(input)
+    }
+}
+
+impl<T: PtxScalar + PartialOrd> RandomTest for Setp<T> {
+    fn generate<R: Rng>(rng: &mut R) -> Self::Input {
+        assert_eq!(T::size_of(), 4);
+        let (a, b) = rng.gen::<(u32, u32)>();
+        unsafe { (mem::transmute_copy(&a), mem::transmute_copy(&b)) }
+    }
+}
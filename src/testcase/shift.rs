@@ -1,4 +1,4 @@
-use crate::test::{make_range, PtxScalar, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, PtxScalar, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use num::PrimInt;
 use std::mem;
 
@@ -8,19 +8,26 @@ pub fn all_tests() -> Vec<TestCase> {
     vec![
         TestCase::new(
             "shl_b16".to_string(),
+            shift_tags("shl", "b16"),
             make_range(Shl {}),
         ),
         TestCase::new(
             "shr_u16".to_string(),
+            shift_tags("shr", "u16"),
             make_range::<Shr<u16>>(Shr { _phantom: std::marker::PhantomData }),
         ),
         TestCase::new(
             "shr_s16".to_string(),
+            shift_tags("shr", "s16"),
             make_range::<Shr<i16>>(Shr { _phantom: std::marker::PhantomData }),
         ),
     ]
 }
 
+fn shift_tags(instruction: &'static str, ty: &str) -> Tags {
+    Tags { instruction, types: vec![ty.to_string()], ..Default::default() }
+}
+
 struct Shl {}
 
 impl TestPtx for Shl {
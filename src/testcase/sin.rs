@@ -1,5 +1,5 @@
 use crate::common::{self, flush_to_zero_f32};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use core::f32;
 use std::mem;
 
@@ -15,8 +15,9 @@ pub fn all_tests() -> Vec<TestCase> {
 
 fn sin(ftz: bool) -> TestCase {
     let test = make_range(Sin { ftz });
+    let tags = Tags { instruction: "sin", types: vec!["f32".to_string()], rounding: Some("approx"), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("sin_approx{}", ftz), test)
+    TestCase::new(format!("sin_approx{}", ftz), tags, test)
 }
 
 pub struct Sin {
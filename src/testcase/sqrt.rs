@@ -1,5 +1,5 @@
 use crate::common::{self, flush_to_zero_f32, Rounding};
-use crate::test::{make_range, RangeTest, TestCase, TestCommon, TestPtx};
+use crate::test::{make_range, RangeTest, Tags, TestCase, TestCommon, TestPtx};
 use std::mem;
 
 pub static PTX: &str = include_str!("sqrt.ptx");
@@ -29,8 +29,9 @@ fn sqrt_approx(ftz: bool) -> TestCase {
 fn sqrt<const APPROX: bool>(rnd: Rounding, ftz: bool) -> TestCase {
     let test = make_range::<Sqrt<APPROX>>(Sqrt { rnd, ftz });
     let mode = if APPROX { "approx" } else { rnd.as_str() };
+    let tags = Tags { instruction: "sqrt", types: vec!["f32".to_string()], rounding: Some(mode), ftz, ..Default::default() };
     let ftz = if ftz { "_ftz" } else { "" };
-    TestCase::new(format!("sqrt_{}{}", mode, ftz), test)
+    TestCase::new(format!("sqrt_{}{}", mode, ftz), tags, test)
 }
 
 pub struct Sqrt<const APPROX: bool> {